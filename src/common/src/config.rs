@@ -0,0 +1,58 @@
+use std::path::PathBuf;
+
+fn env_path(name: &str) -> Option<PathBuf> {
+  match std::env::var_os(name) {
+    Some(ref value) if !value.is_empty() => Some(PathBuf::from(value)),
+    _ => None
+  }
+}
+
+// $XDG_CONFIG_HOME, ~/.config or %APPDATA%
+fn get_base_config_dir() -> Option<PathBuf> {
+  if cfg!(windows) {
+    return env_path("APPDATA");
+  }
+
+  if let Some(path) = env_path("XDG_CONFIG_HOME") {
+    return Some(path);
+  }
+
+  if let Some(path) = env_path("HOME") {
+    return Some(path.join(".config"));
+  }
+
+  None
+}
+
+pub fn get_config_dir() -> Option<PathBuf> {
+  if let Some(path) = get_base_config_dir() {
+    Some(path.join("opensrme"))
+  } else {
+    None
+  }
+}
+
+pub fn get_config_path(filename: &str) -> Option<PathBuf> {
+  if let Some(path) = get_config_dir() {
+    Some(path.join(filename))
+  } else {
+    None
+  }
+}
+
+pub fn read_config_file(filename: &str) -> std::io::Result<String> {
+  if let Some(path) = get_config_path(filename) {
+    std::fs::read_to_string(path)
+  } else {
+    Err(std::io::Error::new(std::io::ErrorKind::NotFound, "No configuration directory"))
+  }
+}
+
+pub fn write_config_file(filename: &str, contents: &str) -> std::io::Result<()> {
+  if let Some(dir) = get_config_dir() {
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(dir.join(filename), contents)
+  } else {
+    Err(std::io::Error::new(std::io::ErrorKind::NotFound, "No configuration directory"))
+  }
+}
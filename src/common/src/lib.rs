@@ -20,3 +20,6 @@ pub use platform_sdl::*;
 
 mod time;
 pub use time::*;
+
+mod config;
+pub use config::*;
@@ -45,10 +45,44 @@ pub enum MouseScroll {
   Down
 }
 
+pub type ControllerId = u32;
+
+#[derive(Hash, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ControllerButton {
+  Unknown,
+  A,
+  B,
+  X,
+  Y,
+  Back,
+  Guide,
+  Start,
+  LeftStick,
+  RightStick,
+  LeftShoulder,
+  RightShoulder,
+  DPadUp,
+  DPadDown,
+  DPadLeft,
+  DPadRight
+}
+
+#[derive(Hash, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ControllerAxis {
+  LeftX,
+  LeftY,
+  RightX,
+  RightY,
+  LeftTrigger,
+  RightTrigger
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Event {
   Quit,
   Resize(Vec3i),
+  // whatever was drawn into render targets is gone, they have to be made and drawn again
+  RenderTargetsReset,
   Key {
     pressed: bool,
     key: Key
@@ -58,9 +92,24 @@ pub enum Event {
     button: MouseButton
   },
   MouseScroll(MouseScroll),
+  // typed text, after the keyboard layout is applied. one event per character
+  TextInput(char),
   MousePos {
     pos: Vec3i,
     delta: Vec3i
+  },
+  ControllerAdded(ControllerId),
+  ControllerRemoved(ControllerId),
+  ControllerButton {
+    controller: ControllerId,
+    pressed: bool,
+    button: ControllerButton
+  },
+  ControllerAxis {
+    controller: ControllerId,
+    axis: ControllerAxis,
+    // -1 to 1 for sticks (+y is down), 0 to 1 for triggers
+    value: FScalar
   }
 }
 
@@ -74,8 +123,34 @@ pub struct Rotate {
   pub origin: Vec3i
 }
 
+// dx and dy are relative to the middle of the arc, with y pointing down
+fn arc_contains_point(start_angle: IScalar, arc_angle: IScalar, dx: FScalar, dy: FScalar) -> bool {
+  if arc_angle.abs() >= 360 {
+    return true;
+  }
+
+  let angle = (-dy).atan2(dx).to_degrees();
+  let (start, length) = if arc_angle < 0 {
+    (start_angle + arc_angle, -arc_angle)
+  } else {
+    (start_angle, arc_angle)
+  };
+
+  let mut offset = (angle - start as FScalar) % 360.;
+  if offset < 0. {
+    offset += 360.;
+  }
+
+  offset <= length as FScalar
+}
+
 pub trait Platform {
-  fn new(title: &str, width: i16, height: i16) -> Self where Self: Sized;
+  fn new(title: &str, width: i16, height: i16) -> Self where Self: Sized {
+    Self::new_with_vsync(title, width, height, false)
+  }
+  fn new_with_vsync(title: &str, width: i16, height: i16, vsync: bool) -> Self where Self: Sized;
+  // whether swap() waits for the display's vertical refresh
+  fn has_vsync(&self) -> bool { false }
   fn close_window(&mut self);
   //fn wait_event(&mut self, window: PlatformId);
   fn poll_event(&mut self) -> Option<Event>;
@@ -111,6 +186,13 @@ pub trait Platform {
   }
   fn unload_image(&mut self, image: PlatformId);
 
+  // an empty image that can be drawn into, and drawn like any other image afterwards.
+  // unloaded with unload_image. None if the platform can't make one
+  fn new_render_target(&mut self, size: Vec3i) -> Option<PlatformId>;
+  // None goes back to drawing to the window. translation and scale still apply
+  fn set_render_target(&mut self, target: Option<PlatformId>);
+  fn get_render_target(&self) -> Option<PlatformId>;
+
   fn reset(&mut self) {
     self.reset_translation();
     self.reset_scale();
@@ -132,10 +214,123 @@ pub trait Platform {
                  flip: Flip,
                  rotate: Option<Rotate>,
                  x_dest: IScalar, y_dest: IScalar);
+  // the whole image stretched over the rectangle, ignoring translation and scale.
+  // scaled with nearest neighbor filtering
+  fn draw_image_scaled(&mut self, image: PlatformId, pos: Vec3i, size: Vec3i);
   fn fill_rect(&mut self, x: IScalar, y: IScalar, width: IScalar, height: IScalar);
   fn fill_rect_vec(&mut self, pos: Vec3i, size: Vec3i) {
     self.fill_rect(pos.x, pos.y, size.x, size.y);
   }
 
+  // the shapes below are built out of fill_rect, so they're scaled the same way as everything else.
+  // like J2ME, outlines cover width + 1 and height + 1 pixels, and arc angles are in degrees,
+  // counter-clockwise from 3 o'clock
+
+  fn draw_line(&mut self, x1: IScalar, y1: IScalar, x2: IScalar, y2: IScalar) {
+    // bresenham
+    let dx = (x2 - x1).abs();
+    let dy = -(y2 - y1).abs();
+    let step_x = if x1 < x2 { 1 } else { -1 };
+    let step_y = if y1 < y2 { 1 } else { -1 };
+
+    let mut x = x1;
+    let mut y = y1;
+    let mut error = dx + dy;
+
+    loop {
+      self.fill_rect(x, y, 1, 1);
+
+      if x == x2 && y == y2 {
+        break;
+      }
+
+      let error2 = error * 2;
+      if error2 >= dy {
+        error += dy;
+        x += step_x;
+      }
+
+      if error2 <= dx {
+        error += dx;
+        y += step_y;
+      }
+    }
+  }
+
+  fn draw_rect(&mut self, x: IScalar, y: IScalar, width: IScalar, height: IScalar) {
+    if width < 0 || height < 0 {
+      return;
+    }
+
+    // a line, the sides would overlap the top and bottom
+    if width == 0 || height == 0 {
+      self.fill_rect(x, y, width + 1, height + 1);
+      return;
+    }
+
+    self.fill_rect(x, y, width + 1, 1);
+    self.fill_rect(x, y + height, width + 1, 1);
+    self.fill_rect(x, y + 1, 1, height - 1);
+    self.fill_rect(x + width, y + 1, 1, height - 1);
+  }
+
+  fn fill_arc(&mut self, x: IScalar, y: IScalar, width: IScalar, height: IScalar, start_angle: IScalar, arc_angle: IScalar) {
+    if width <= 0 || height <= 0 {
+      return;
+    }
+
+    let radius_x = width as FScalar / 2.;
+    let radius_y = height as FScalar / 2.;
+
+    // one rect per run of covered pixels in each row
+    for row in 0..height {
+      let dy = row as FScalar + 0.5 - radius_y;
+      let mut run_start = None;
+
+      for column in 0..width + 1 {
+        let dx = column as FScalar + 0.5 - radius_x;
+
+        let covered = column < width &&
+          (dx / radius_x).powi(2) + (dy / radius_y).powi(2) <= 1. &&
+          arc_contains_point(start_angle, arc_angle, dx, dy);
+
+        match (covered, run_start) {
+          (true, None) => run_start = Some(column),
+          (false, Some(start)) => {
+            self.fill_rect(x + start, y + row, column - start, 1);
+            run_start = None;
+          },
+          _ => {}
+        }
+      }
+    }
+  }
+
+  fn draw_arc(&mut self, x: IScalar, y: IScalar, width: IScalar, height: IScalar, start_angle: IScalar, arc_angle: IScalar) {
+    if width < 0 || height < 0 {
+      return;
+    }
+
+    let radius_x = width as FScalar / 2.;
+    let radius_y = height as FScalar / 2.;
+    let arc_angle = if arc_angle.abs() > 360 { 360 * arc_angle.signum() } else { arc_angle };
+
+    // enough steps to not leave gaps along the outline
+    let steps = std::cmp::max(8, ((radius_x + radius_y) * std::f64::consts::PI * 2.) as IScalar);
+    let mut last = None;
+
+    for i in 0..steps + 1 {
+      let degrees = start_angle as FScalar + arc_angle as FScalar * i as FScalar / steps as FScalar;
+      let radians = degrees.to_radians();
+
+      let point = Vec3i::new2(x + (radius_x + radius_x * radians.cos()).round() as IScalar,
+                              y + (radius_y - radius_y * radians.sin()).round() as IScalar);
+      if last != Some(point) {
+        self.fill_rect(point.x, point.y, 1, 1);
+        last = Some(point);
+      }
+    }
+  }
+
   fn swap(&mut self);
 }
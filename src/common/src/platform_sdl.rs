@@ -10,11 +10,18 @@ pub struct SDL2Platform {
   sdl_context: sdl2::Sdl,
   sdl_video: sdl2::VideoSubsystem,
   sdl_events: sdl2::EventPump,
+  sdl_controller: Option<sdl2::GameControllerSubsystem>,
+  controllers: HashMap<ControllerId, sdl2::controller::GameController>,
   sdl_canvas: sdl2::render::Canvas<sdl2::video::Window>,
   texture_creator: sdl2::render::TextureCreator<sdl2::video::WindowContext>,
 
   textures: HashMap<PlatformId, sdl2::render::Texture>,
   image_sizes: HashMap<PlatformId, Vec3i>,
+  render_target: Option<PlatformId>,
+  // events that came in together, like the characters of a single text input
+  pending_events: VecDeque<Event>,
+
+  vsync: bool,
 
   offset: Vec3i,
   scale: FScalar
@@ -75,6 +82,55 @@ fn mouseb_event(pressed: bool, mouse_btn: sdl2::mouse::MouseButton) -> Event {
   }
 }
 
+fn controllerb_event(which: u32, pressed: bool, button: sdl2::controller::Button) -> Event {
+  use sdl2::controller::Button;
+
+  Event::ControllerButton {
+    controller: which as ControllerId,
+    pressed,
+    button: match button {
+      Button::A             => ControllerButton::A,
+      Button::B             => ControllerButton::B,
+      Button::X             => ControllerButton::X,
+      Button::Y             => ControllerButton::Y,
+      Button::Back          => ControllerButton::Back,
+      Button::Guide         => ControllerButton::Guide,
+      Button::Start         => ControllerButton::Start,
+      Button::LeftStick     => ControllerButton::LeftStick,
+      Button::RightStick    => ControllerButton::RightStick,
+      Button::LeftShoulder  => ControllerButton::LeftShoulder,
+      Button::RightShoulder => ControllerButton::RightShoulder,
+      Button::DPadUp        => ControllerButton::DPadUp,
+      Button::DPadDown      => ControllerButton::DPadDown,
+      Button::DPadLeft      => ControllerButton::DPadLeft,
+      Button::DPadRight     => ControllerButton::DPadRight,
+      _                     => ControllerButton::Unknown
+    }
+  }
+}
+
+fn controllera_event(which: u32, axis: sdl2::controller::Axis, value: i16) -> Event {
+  use sdl2::controller::Axis;
+
+  let mut value = value as FScalar / 32767.;
+  if value < -1. {
+    value = -1.;
+  }
+
+  Event::ControllerAxis {
+    controller: which as ControllerId,
+    axis: match axis {
+      Axis::LeftX        => ControllerAxis::LeftX,
+      Axis::LeftY        => ControllerAxis::LeftY,
+      Axis::RightX       => ControllerAxis::RightX,
+      Axis::RightY       => ControllerAxis::RightY,
+      Axis::TriggerLeft  => ControllerAxis::LeftTrigger,
+      Axis::TriggerRight => ControllerAxis::RightTrigger
+    },
+    value
+  }
+}
+
 /*fn get_key(button: ButtonArgs, key: PKey) -> Key {
   Key {
     value: key_to_ascii(key),
@@ -95,6 +151,9 @@ fn get_event(event: SEvent) -> Option<Event> {
         delta: Vec3i::new2(xrel, yrel)
       }
     },
+    SEvent::ControllerButtonDown { which, button, .. } => controllerb_event(which, true, button),
+    SEvent::ControllerButtonUp { which, button, .. }   => controllerb_event(which, false, button),
+    SEvent::ControllerAxisMotion { which, axis, value, .. } => controllera_event(which, axis, value),
     SEvent::MouseWheel { y, .. } => {
       Event::MouseScroll(
         if y < 0 {
@@ -114,6 +173,8 @@ fn get_event(event: SEvent) -> Option<Event> {
         _                                           => return None
       }
     },
+    SEvent::RenderTargetsReset {..} |
+    SEvent::RenderDeviceReset {..}            => Event::RenderTargetsReset,
     _                                         => return None
   })
 }
@@ -135,20 +196,70 @@ fn create_scaled_rect(
               iscale(scale, width), iscale(scale, height))
 }
 
+impl SDL2Platform {
+  fn open_controller(&mut self, joystick_index: u32) -> Option<Event> {
+    if let Some(ref subsystem) = self.sdl_controller {
+      match subsystem.open(joystick_index) {
+        Ok(controller) => {
+          let id = controller.instance_id() as ControllerId;
+          println!("Controller connected: {}", controller.name());
+
+          self.controllers.insert(id, controller);
+          return Some(Event::ControllerAdded(id));
+        },
+        Err(error) => {
+          println!("Unable to open controller #{}: {}", joystick_index, error);
+        }
+      }
+    }
+
+    None
+  }
+
+  fn close_controller(&mut self, which: u32) -> Option<Event> {
+    let id = which as ControllerId;
+
+    if let Some(_) = self.controllers.remove(&id) {
+      Some(Event::ControllerRemoved(id))
+    } else {
+      None
+    }
+  }
+}
+
 // rewrite to use gfx/glium + gfx_graphics
 // or rewrite so that event loop is on own thread
 impl Platform for SDL2Platform {
-  fn new(title: &str, width: i16, height: i16) -> Self {
+  fn new_with_vsync(title: &str, width: i16, height: i16, vsync: bool) -> Self {
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
     let eventpump = sdl_context.event_pump().unwrap();
 
+    // already connected controllers are reported through ControllerDeviceAdded events
+    let controller_subsystem = match sdl_context.game_controller() {
+      Ok(subsystem) => Some(subsystem),
+      Err(error) => {
+        println!("Unable to initialize game controllers: {}", error);
+        None
+      }
+    };
+
     let window = video_subsystem.window(
       title,
       width as u32, height as u32
     ).build().unwrap();
 
-    let mut canvas = window.into_canvas().accelerated().build().unwrap();
+    // textures are read when they're created, scaled pixel art should stay sharp
+    sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "nearest");
+
+    let mut canvas_builder = window.into_canvas().accelerated();
+    if vsync {
+      canvas_builder = canvas_builder.present_vsync();
+    }
+
+    let mut canvas = canvas_builder.build().unwrap();
+    // the driver is free to ignore the vsync request
+    let vsync = (canvas.info().flags & sdl2::sys::SDL_RendererFlags::SDL_RENDERER_PRESENTVSYNC as u32) != 0;
     canvas.set_blend_mode(sdl2::render::BlendMode::Blend);
     let texture_creator = canvas.texture_creator();
 
@@ -157,22 +268,43 @@ impl Platform for SDL2Platform {
       sdl_context,
       sdl_video: video_subsystem,
       sdl_events: eventpump,
+      sdl_controller: controller_subsystem,
+      controllers: HashMap::new(),
       sdl_canvas: canvas,
       texture_creator: texture_creator,
       textures: HashMap::new(),
       image_sizes: HashMap::new(),
+      render_target: None,
+      pending_events: VecDeque::new(),
+      vsync,
       offset: Vec3i::new2(0, 0),
       scale: 1.
     }
   }
 
+  fn has_vsync(&self) -> bool {
+    self.vsync
+  }
+
   fn close_window(&mut self) {
     self.sdl_canvas.window_mut().hide();
   }
 
   fn poll_event(&mut self) -> Option<Event> {
+    if let Some(event) = self.pending_events.pop_front() {
+      return Some(event);
+    }
+
     if let Some(event) = self.sdl_events.poll_event() {
-      get_event(event)
+      match event {
+        SEvent::ControllerDeviceAdded { which, .. } => self.open_controller(which),
+        SEvent::ControllerDeviceRemoved { which, .. } => self.close_controller(which),
+        SEvent::TextInput { text, .. } => {
+          self.pending_events.extend(text.chars().map(Event::TextInput));
+          self.pending_events.pop_front()
+        },
+        _ => get_event(event)
+      }
     } else {
       None
     }
@@ -215,10 +347,46 @@ impl Platform for SDL2Platform {
 
   fn unload_image(&mut self, image: PlatformId) {
     if let Some(_) = self.textures.remove(&image) {
+      self.image_sizes.remove(&image);
       self.cache.free_id(image);
     }
   }
 
+  fn new_render_target(&mut self, size: Vec3i) -> Option<PlatformId> {
+    let texture = self.texture_creator.create_texture_target(Some(sdl2::pixels::PixelFormatEnum::ABGR8888),
+                                                             size.x as u32,
+                                                             size.y as u32);
+
+    if let Ok(mut texture) = texture {
+      texture.set_blend_mode(sdl2::render::BlendMode::Blend);
+
+      let id = self.cache.get_id();
+      self.textures.insert(id, texture);
+      self.image_sizes.insert(id, size);
+      Some(id)
+    } else {
+      None
+    }
+  }
+
+  fn set_render_target(&mut self, target: Option<PlatformId>) {
+    // the canvas only offers a closure for this, which doesn't fit drawing through the trait
+    let raw_texture = match target.and_then(|target| self.textures.get(&target)) {
+      Some(texture) => texture.raw(),
+      None => std::ptr::null_mut()
+    };
+
+    unsafe {
+      sdl2::sys::SDL_SetRenderTarget(self.sdl_canvas.raw(), raw_texture);
+    }
+
+    self.render_target = if raw_texture.is_null() { None } else { target };
+  }
+
+  fn get_render_target(&self) -> Option<PlatformId> {
+    self.render_target
+  }
+
   fn reset_translation(&mut self) {
     self.offset = Vec3i::default();
   }
@@ -284,6 +452,12 @@ impl Platform for SDL2Platform {
     }
   }
 
+  fn draw_image_scaled(&mut self, image: PlatformId, pos: Vec3i, size: Vec3i) {
+    if let Some(image) = self.textures.get(&image) {
+      self.sdl_canvas.copy(image, None, create_rect(pos.x, pos.y, size.x, size.y)).unwrap();
+    }
+  }
+
   fn fill_rect(&mut self, x: IScalar, y: IScalar, width: IScalar, height: IScalar) {
     self.sdl_canvas.fill_rect(Some(create_scaled_rect(self.scale, x + self.offset.x, y + self.offset.y, width, height))).unwrap();
   }
@@ -0,0 +1,189 @@
+use super::*;
+use entity::{EntityBase, EntityStance};
+
+// not in the original game, which plays every clip at the same speed
+
+// how long a clip takes to play once
+const CLIP_LENGTH: Time = 700;
+// walking and running clips are played at the normal speed when moving this fast
+const WALK_REFERENCE_SPEED: FScalar = person::PLAYER_SPEED / 2.;
+const RUN_REFERENCE_SPEED: FScalar = person::PLAYER_SPEED;
+const MIN_PLAYBACK_RATE: FScalar = 0.25;
+const MAX_PLAYBACK_RATE: FScalar = 2.;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Playback {
+  Loop,
+  // stays on the last frame afterwards, and reports that it finished once
+  Once,
+  // a single frame picked by the entity's state, like a broken car
+  Hold(usize)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Animation {
+  pub clip: ClipId,
+  pub playback: Playback,
+  pub length: Time,
+  // time scale, for matching the movement speed
+  pub rate: FScalar,
+  // walking and running, switching between them keeps the step they were at
+  pub stride: bool
+}
+
+fn get_person_animation(context: &globals::Context, entity: &EntityBase) -> Option<Animation> {
+  let mut stance = entity.stance;
+  if stance == EntityStance::Riding {
+    return None;
+  }
+
+  if stance == EntityStance::Unknown {
+    stance = EntityStance::Running;
+  }
+
+  let class = entity.get_class(context);
+  let clip = class.clip + stance as i32;
+  if clip < 0 || clip as usize >= context.data.clips.len() {
+    return None;
+  }
+
+  let (playback, rate) = match stance {
+    EntityStance::Walking => (Playback::Loop, entity.speed / WALK_REFERENCE_SPEED),
+    EntityStance::Running => (Playback::Loop, entity.speed / RUN_REFERENCE_SPEED),
+    EntityStance::Punching | EntityStance::Shooting => (Playback::Once, 1.),
+    _ => (Playback::Loop, 1.)
+  };
+
+  Some(Animation {
+    clip,
+    playback,
+    length: CLIP_LENGTH,
+    rate: rate.max(MIN_PLAYBACK_RATE).min(MAX_PLAYBACK_RATE),
+    stride: stance.is_self_moving()
+  })
+}
+
+fn get_vehicle_animation(context: &globals::Context, entity: &EntityBase) -> Option<Animation> {
+  let class = entity.get_class(context);
+  if class.clip < 0 || class.clip as usize >= context.data.clips.len() {
+    return None;
+  }
+
+  // the broken car is the last frame, and a badly damaged one the frame before it if the clip has one
+  let frames = context.data.clips[class.clip as usize].first().map_or(0, |frames| frames.len());
+  let frame = if entity.broken {
+    frames.saturating_sub(1)
+  } else if frames > 2 && vehicle::is_damaged(context, entity) {
+    frames - 2
+  } else {
+    0
+  };
+
+  Some(Animation {
+    clip: class.clip,
+    playback: Playback::Hold(frame),
+    length: CLIP_LENGTH,
+    rate: 1.,
+    stride: false
+  })
+}
+
+pub fn get_animation(context: &globals::Context, entity: &EntityBase) -> Option<Animation> {
+  if entity.entity_type.is_person() {
+    get_person_animation(context, entity)
+  } else if entity.entity_type.is_vehicle() {
+    get_vehicle_animation(context, entity)
+  } else {
+    None
+  }
+}
+
+// picks the entity's clip from its state every step, and keeps track of how far it's played
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AnimationController {
+  pub animation: Option<Animation>,
+  // in milliseconds at the normal rate
+  pub progress: FScalar,
+  pub finished: bool
+}
+
+impl AnimationController {
+  pub fn new() -> Self {
+    AnimationController::default()
+  }
+
+  fn set_animation(&mut self, animation: Option<Animation>) {
+    let old = self.animation;
+    self.animation = animation;
+
+    match (old, animation) {
+      // only the rate changed
+      (Some(old), Some(new)) if old.clip == new.clip && old.playback == new.playback => {},
+      (Some(old), Some(new)) if old.stride && new.stride => {
+        self.progress = (self.progress / old.length as FScalar).fract() * new.length as FScalar;
+      },
+      _ => {
+        self.progress = 0.;
+        self.finished = false;
+      }
+    }
+  }
+
+  // returns true when a one-shot animation just finished
+  pub fn step(&mut self, animation: Option<Animation>, delta: Time) -> bool {
+    self.set_animation(animation);
+
+    let animation = match self.animation {
+      Some(animation) => animation,
+      None => return false
+    };
+
+    self.progress += delta as FScalar * animation.rate;
+
+    match animation.playback {
+      Playback::Loop => {
+        self.progress %= animation.length as FScalar;
+        false
+      },
+      Playback::Once => {
+        if self.finished || self.progress < animation.length as FScalar {
+          return false;
+        }
+
+        self.progress = animation.length as FScalar;
+        self.finished = true;
+        true
+      },
+      Playback::Hold(_) => false
+    }
+  }
+
+  pub fn get_frame(&self, frames: usize) -> usize {
+    let animation = match self.animation {
+      Some(animation) => animation,
+      None => return 0
+    };
+
+    let frame = ((self.progress / animation.length as FScalar) * frames as FScalar) as usize;
+
+    match animation.playback {
+      Playback::Loop => frame % frames,
+      Playback::Once => std::cmp::min(frame, frames - 1),
+      Playback::Hold(frame) => std::cmp::min(frame, frames - 1)
+    }
+  }
+
+  pub fn get_sprite(&self, context: &globals::Context, angle: Angle) -> Option<SpriteId> {
+    let clip = &context.data.clips[self.animation?.clip as usize];
+    if clip.is_empty() {
+      return None;
+    }
+
+    let clip_angle = &clip[util::get_angle_in_clip(angle, clip.len())];
+    if clip_angle.is_empty() {
+      return None;
+    }
+
+    Some(clip_angle[self.get_frame(clip_angle.len())])
+  }
+}
@@ -195,6 +195,7 @@ fn read_sprites<T: DataInputStream>(file: &mut T, context: &mut DataContext) ->
     sprite_id += 1;
   }*/
 
+  compile_sprites(&mut sprites);
   context.sprites = sprites;
 
   Ok(())
@@ -0,0 +1,392 @@
+use opensrme_common::*;
+use input::InputKey;
+use std::io;
+
+const BINDINGS_FILENAME: &str = "sr2_input.cfg";
+
+// how far a trigger has to be pulled to count as pressed
+pub const TRIGGER_THRESHOLD: FScalar = 0.5;
+
+// SDL scancodes
+pub mod scancodes {
+  pub const A: i32 = 4;
+  pub const D: i32 = 7;
+  pub const E: i32 = 8;
+  pub const F: i32 = 9;
+  pub const S: i32 = 22;
+  pub const V: i32 = 25;
+  pub const W: i32 = 26;
+  pub const RETURN: i32 = 40;
+  pub const ESCAPE: i32 = 41;
+  pub const BACKSPACE: i32 = 42;
+  pub const SPACE: i32 = 44;
+  pub const GRAVE: i32 = 53;
+  pub const F1: i32 = 58;
+  pub const F2: i32 = 59;
+  pub const F3: i32 = 60;
+  pub const DOWN: i32 = 81;
+  pub const UP: i32 = 82;
+}
+
+#[derive(Hash, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Binding {
+  // physical key, independent of the keyboard layout
+  Scancode(i32),
+  // key producing this character in the current layout
+  Char(u8),
+  Mouse(MouseButton),
+  // triggers are axes, pressed past TRIGGER_THRESHOLD
+  Controller(ControllerButton),
+  ControllerTrigger(ControllerAxis)
+}
+
+const CONTROLLER_BUTTON_NAMES: &[(ControllerButton, &str)] = &[
+  (ControllerButton::A, "a"),
+  (ControllerButton::B, "b"),
+  (ControllerButton::X, "x"),
+  (ControllerButton::Y, "y"),
+  (ControllerButton::Back, "back"),
+  (ControllerButton::Guide, "guide"),
+  (ControllerButton::Start, "start"),
+  (ControllerButton::LeftStick, "leftstick"),
+  (ControllerButton::RightStick, "rightstick"),
+  (ControllerButton::LeftShoulder, "leftshoulder"),
+  (ControllerButton::RightShoulder, "rightshoulder"),
+  (ControllerButton::DPadUp, "dpup"),
+  (ControllerButton::DPadDown, "dpdown"),
+  (ControllerButton::DPadLeft, "dpleft"),
+  (ControllerButton::DPadRight, "dpright")
+];
+
+const CONTROLLER_TRIGGER_NAMES: &[(ControllerAxis, &str)] = &[
+  (ControllerAxis::LeftTrigger, "lefttrigger"),
+  (ControllerAxis::RightTrigger, "righttrigger")
+];
+
+fn controller_button_name(button: ControllerButton) -> &'static str {
+  for item in CONTROLLER_BUTTON_NAMES.iter() {
+    if item.0 == button {
+      return item.1;
+    }
+  }
+
+  "unknown"
+}
+
+fn controller_trigger_name(axis: ControllerAxis) -> &'static str {
+  for item in CONTROLLER_TRIGGER_NAMES.iter() {
+    if item.0 == axis {
+      return item.1;
+    }
+  }
+
+  "unknown"
+}
+
+fn scancode_name(scancode: i32) -> Option<String> {
+  Some(match scancode {
+    4..=29 => ((('A' as u8) + (scancode - 4) as u8) as char).to_string(),
+    30..=38 => ((('1' as u8) + (scancode - 30) as u8) as char).to_string(),
+    39 => "0".to_string(),
+    40 => "Return".to_string(),
+    41 => "Escape".to_string(),
+    42 => "Backspace".to_string(),
+    43 => "Tab".to_string(),
+    44 => "Space".to_string(),
+    58..=69 => format!("F{}", scancode - 57),
+    79 => "Right".to_string(),
+    80 => "Left".to_string(),
+    81 => "Down".to_string(),
+    82 => "Up".to_string(),
+    224 => "LCtrl".to_string(),
+    225 => "LShift".to_string(),
+    226 => "LAlt".to_string(),
+    228 => "RCtrl".to_string(),
+    229 => "RShift".to_string(),
+    230 => "RAlt".to_string(),
+    _ => return None
+  })
+}
+
+fn mouse_button_name(button: MouseButton) -> &'static str {
+  match button {
+    MouseButton::Left    => "left",
+    MouseButton::Right   => "right",
+    MouseButton::Middle  => "middle",
+    MouseButton::Unknown => "unknown"
+  }
+}
+
+fn mouse_button_from_name(name: &str) -> Option<MouseButton> {
+  match name {
+    "left"   => Some(MouseButton::Left),
+    "right"  => Some(MouseButton::Right),
+    "middle" => Some(MouseButton::Middle),
+    _        => None
+  }
+}
+
+fn controller_binding_from_name(name: &str) -> Option<Binding> {
+  for item in CONTROLLER_BUTTON_NAMES.iter() {
+    if item.1 == name {
+      return Some(Binding::Controller(item.0));
+    }
+  }
+
+  for item in CONTROLLER_TRIGGER_NAMES.iter() {
+    if item.1 == name {
+      return Some(Binding::ControllerTrigger(item.0));
+    }
+  }
+
+  None
+}
+
+impl Binding {
+  pub fn from_event(event: Event) -> Option<Binding> {
+    match event {
+      Event::Key { pressed: true, key } => Some(Binding::Scancode(key.scancode)),
+      Event::MouseButton { pressed: true, button } => {
+        if button == MouseButton::Unknown {
+          None
+        } else {
+          Some(Binding::Mouse(button))
+        }
+      },
+      Event::ControllerButton { pressed: true, button, .. } => {
+        if button == ControllerButton::Unknown {
+          None
+        } else {
+          Some(Binding::Controller(button))
+        }
+      },
+      Event::ControllerAxis { axis, value, .. } => {
+        let is_trigger = axis == ControllerAxis::LeftTrigger || axis == ControllerAxis::RightTrigger;
+
+        if is_trigger && value >= TRIGGER_THRESHOLD {
+          Some(Binding::ControllerTrigger(axis))
+        } else {
+          None
+        }
+      },
+      _ => None
+    }
+  }
+
+  // used in the rebinding menu
+  pub fn display_name(&self) -> String {
+    match *self {
+      Binding::Scancode(scancode) => {
+        scancode_name(scancode).unwrap_or(format!("Key #{}", scancode))
+      },
+      Binding::Char(value) => format!("'{}'", value as char),
+      Binding::Mouse(button) => format!("Mouse {}", mouse_button_name(button)),
+      Binding::Controller(button) => format!("Pad {}", controller_button_name(button)),
+      Binding::ControllerTrigger(axis) => format!("Pad {}", controller_trigger_name(axis))
+    }
+  }
+
+  // used in the config file
+  pub fn to_config(&self) -> String {
+    match *self {
+      Binding::Scancode(scancode) => format!("scancode:{}", scancode),
+      Binding::Char(value) => format!("char:{}", value),
+      Binding::Mouse(button) => format!("mouse:{}", mouse_button_name(button)),
+      Binding::Controller(button) => format!("controller:{}", controller_button_name(button)),
+      Binding::ControllerTrigger(axis) => format!("controller:{}", controller_trigger_name(axis))
+    }
+  }
+
+  pub fn from_config(text: &str) -> Option<Binding> {
+    let mut split = text.trim().splitn(2, ':');
+    let kind = split.next()?.trim();
+    let value = split.next()?.trim();
+
+    match kind {
+      "scancode" => value.parse::<i32>().ok().map(Binding::Scancode),
+      "char" => value.parse::<u8>().ok().map(Binding::Char),
+      "mouse" => mouse_button_from_name(value).map(Binding::Mouse),
+      "controller" => controller_binding_from_name(value),
+      _ => None
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputBindings {
+  // ordered by InputKey::all()
+  pub bindings: Vec<(InputKey, Vec<Binding>)>
+}
+
+impl Default for InputBindings {
+  fn default() -> Self {
+    let mut bindings = InputBindings {
+      bindings: vec![]
+    };
+
+    for key in InputKey::all().iter() {
+      bindings.bindings.push((*key, vec![]));
+    }
+
+    bindings.add(InputKey::Up, Binding::Scancode(scancodes::W));
+    bindings.add(InputKey::Left, Binding::Scancode(scancodes::A));
+    bindings.add(InputKey::Down, Binding::Scancode(scancodes::S));
+    bindings.add(InputKey::Right, Binding::Scancode(scancodes::D));
+    bindings.add(InputKey::Context, Binding::Scancode(scancodes::E));
+    bindings.add(InputKey::Attack, Binding::Scancode(scancodes::F));
+    bindings.add(InputKey::Vehicle, Binding::Scancode(scancodes::V));
+    bindings.add(InputKey::Exit, Binding::Scancode(scancodes::ESCAPE));
+    bindings.add(InputKey::Rebind, Binding::Scancode(scancodes::F1));
+    bindings.add(InputKey::ToggleControls, Binding::Scancode(scancodes::F2));
+    bindings.add(InputKey::ToggleDebug, Binding::Scancode(scancodes::F3));
+    bindings.add(InputKey::Console, Binding::Scancode(scancodes::GRAVE));
+    bindings.add(InputKey::Handbrake, Binding::Scancode(scancodes::SPACE));
+
+    bindings.add(InputKey::Up, Binding::Controller(ControllerButton::DPadUp));
+    bindings.add(InputKey::Left, Binding::Controller(ControllerButton::DPadLeft));
+    bindings.add(InputKey::Down, Binding::Controller(ControllerButton::DPadDown));
+    bindings.add(InputKey::Right, Binding::Controller(ControllerButton::DPadRight));
+    bindings.add(InputKey::Context, Binding::Controller(ControllerButton::A));
+    bindings.add(InputKey::Attack, Binding::ControllerTrigger(ControllerAxis::RightTrigger));
+    bindings.add(InputKey::Attack, Binding::Controller(ControllerButton::X));
+    bindings.add(InputKey::Vehicle, Binding::Controller(ControllerButton::Y));
+    bindings.add(InputKey::Exit, Binding::Controller(ControllerButton::Back));
+    bindings.add(InputKey::Handbrake, Binding::Controller(ControllerButton::B));
+
+    bindings
+  }
+}
+
+impl InputBindings {
+  pub fn get(&self, key: InputKey) -> &[Binding] {
+    for item in self.bindings.iter() {
+      if item.0 == key {
+        return &item.1[..];
+      }
+    }
+
+    &[]
+  }
+
+  fn get_mut(&mut self, key: InputKey) -> Option<&mut Vec<Binding>> {
+    for item in self.bindings.iter_mut() {
+      if item.0 == key {
+        return Some(&mut item.1);
+      }
+    }
+
+    None
+  }
+
+  pub fn add(&mut self, key: InputKey, binding: Binding) {
+    if let Some(bindings) = self.get_mut(key) {
+      if !bindings.contains(&binding) {
+        bindings.push(binding);
+      }
+    }
+  }
+
+  pub fn clear(&mut self, key: InputKey) {
+    if let Some(bindings) = self.get_mut(key) {
+      bindings.clear();
+    }
+  }
+
+  pub fn lookup(&self, binding: Binding) -> Vec<InputKey> {
+    let mut result = vec![];
+
+    for item in self.bindings.iter() {
+      if item.1.contains(&binding) {
+        result.push(item.0);
+      }
+    }
+
+    result
+  }
+
+  pub fn lookup_key(&self, key: Key) -> Vec<InputKey> {
+    let mut result = self.lookup(Binding::Scancode(key.scancode));
+
+    if key.value != 0 {
+      for inputkey in self.lookup(Binding::Char(key.value)) {
+        if !result.contains(&inputkey) {
+          result.push(inputkey);
+        }
+      }
+    }
+
+    result
+  }
+
+  pub fn to_config(&self) -> String {
+    let mut result = String::from("# action = binding, binding, ...\n");
+    result += "# bindings: scancode:<sdl scancode>, char:<ascii value>, mouse:<left|right|middle>,\n";
+    result += "#   controller:<a|b|x|y|back|guide|start|leftstick|rightstick|leftshoulder|rightshoulder|\n";
+    result += "#               dpup|dpdown|dpleft|dpright|lefttrigger|righttrigger>\n";
+
+    for item in self.bindings.iter() {
+      let bindings: Vec<String> = item.1.iter().map(|binding| binding.to_config()).collect();
+      result += &format!("{} = {}\n", item.0.name(), bindings.join(", "))[..];
+    }
+
+    result
+  }
+
+  pub fn from_config(text: &str) -> io::Result<Self> {
+    let mut bindings = InputBindings::default();
+    let mut seen = vec![];
+
+    for (lineno, line) in text.lines().enumerate() {
+      let line = line.trim();
+      if line.len() == 0 || line.starts_with('#') {
+        continue;
+      }
+
+      let invalid = |message: &str| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("{}:{}: {}", BINDINGS_FILENAME, lineno + 1, message))
+      };
+
+      let mut split = line.splitn(2, '=');
+      let name = split.next().unwrap().trim();
+      let value = split.next().ok_or(invalid("expected '='"))?;
+
+      let key = InputKey::from_name(name).ok_or(invalid("unknown action"))?;
+
+      // actions that appear in the file replace their default bindings
+      if !seen.contains(&key) {
+        bindings.clear(key);
+        seen.push(key);
+      }
+
+      for binding_text in value.split(',') {
+        if binding_text.trim().len() == 0 {
+          continue;
+        }
+
+        let binding = Binding::from_config(binding_text).ok_or(invalid("invalid binding"))?;
+        bindings.add(key, binding);
+      }
+    }
+
+    Ok(bindings)
+  }
+
+  pub fn load() -> io::Result<Self> {
+    InputBindings::from_config(&read_config_file(BINDINGS_FILENAME)?[..])
+  }
+
+  pub fn load_or_default() -> Self {
+    match InputBindings::load() {
+      Ok(bindings) => bindings,
+      Err(ref error) if error.kind() == io::ErrorKind::NotFound => InputBindings::default(),
+      Err(error) => {
+        println!("Unable to load input bindings, using defaults: {}", error);
+        InputBindings::default()
+      }
+    }
+  }
+
+  pub fn save(&self) -> io::Result<()> {
+    write_config_file(BINDINGS_FILENAME, &self.to_config()[..])
+  }
+}
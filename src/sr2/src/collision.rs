@@ -1,6 +1,7 @@
 use super::*;
 use ncollide2d::query::PointQuery;
 use std::sync::Arc;
+use std::collections::HashMap;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Shape {
@@ -92,6 +93,14 @@ impl PhysicalObject {
     self.nashape.contains_point(&self.isometry, &vec_to_napoint(point))
   }
 
+  // radius of a circle containing the whole shape, regardless of the angle
+  pub fn get_radius(&self) -> IScalar {
+    match self.shape {
+      Shape::Circle(radius) => radius,
+      Shape::Rect(vec) => vec.len2().ceil() as IScalar
+    }
+  }
+
   pub fn pos(&self) -> Vec3f {
     navec_to_vec(self.isometry.translation.vector)
   }
@@ -112,6 +121,27 @@ impl PhysicalObject {
     }
   }
 
+  // normal pointing from self towards other, if they're closer than margin
+  pub fn get_contact_normal(&self, other: &PhysicalObject, margin: FScalar) -> Option<Vec3f> {
+    let contact = ncollide2d::query::contact(&self.isometry, &(*self.nashape),
+                                             &other.isometry, &(*other.nashape),
+                                             margin);
+
+    contact.map(|contact| navec_to_vec(contact.normal.into_inner()))
+  }
+
+  // fraction (0..=1) of the movements after which both objects first touch.
+  // only the translation is swept, the angles stay as they are in the isometries.
+  pub fn get_time_of_impact(&self, movement: Vec3f, other: &PhysicalObject, other_movement: Vec3f) -> Option<FScalar> {
+    let toi = ncollide2d::query::time_of_impact(&self.isometry, &vec_to_navec(movement), &(*self.nashape),
+                                                &other.isometry, &vec_to_navec(other_movement), &(*other.nashape));
+
+    match toi {
+      Some(toi) if toi <= 1. => Some(toi),
+      _ => None
+    }
+  }
+
   pub fn collides_with(&self, other: &PhysicalObject) -> bool {
     let proximity = ncollide2d::query::proximity(&self.isometry, &(*self.nashape),
                                                  &other.isometry, &(*other.nashape),
@@ -120,3 +150,87 @@ impl PhysicalObject {
     proximity == ncollide2d::query::Proximity::Intersecting
   }
 }
+
+// Broad-phase: objects are put in every tile their bounding circle touches,
+// so only objects sharing a tile have to be checked against each other.
+pub struct CollisionGrid {
+  cells: HashMap<(IScalar, IScalar), Vec<usize>>,
+  objects: Vec<(usize, Vec3i, Vec3i)>,
+  max_id: usize
+}
+
+impl CollisionGrid {
+  pub fn new() -> Self {
+    CollisionGrid {
+      cells: HashMap::new(),
+      objects: vec![],
+      max_id: 0
+    }
+  }
+
+  pub fn clear(&mut self) {
+    self.cells.clear();
+    self.objects.clear();
+    self.max_id = 0;
+  }
+
+  // prev_pos is where the object started this step, so that fast objects
+  // are paired with everything they could have passed through
+  pub fn insert(&mut self, id: usize, object: &PhysicalObject, prev_pos: Vec3f) {
+    let pos = object.pos();
+    let radius = object.get_radius() as FScalar;
+
+    let min = Vec3f::new2(util::fmin(pos.x, prev_pos.x), util::fmin(pos.y, prev_pos.y));
+    let max = Vec3f::new2(util::fmax(pos.x, prev_pos.x), util::fmax(pos.y, prev_pos.y));
+
+    let start = level::pos_to_tilepos(min - radius);
+    let end   = level::pos_to_tilepos(max + radius);
+
+    for x in start.x..=end.x {
+      for y in start.y..=end.y {
+        self.cells.entry((x, y)).or_insert_with(Vec::new).push(id);
+      }
+    }
+
+    self.objects.push((id, start, end));
+    if id > self.max_id {
+      self.max_id = id;
+    }
+  }
+
+  // (id1, id2) with id1 < id2, sorted, each pair only once
+  pub fn get_pairs(&self) -> Vec<(usize, usize)> {
+    let mut pairs = vec![];
+    let mut last_seen = vec![std::usize::MAX; self.max_id + 1];
+
+    for &(id, start, end) in self.objects.iter() {
+      for x in start.x..=end.x {
+        for y in start.y..=end.y {
+          for &other in self.cells[&(x, y)].iter() {
+            if other > id && last_seen[other] != id {
+              last_seen[other] = id;
+              pairs.push((id, other));
+            }
+          }
+        }
+      }
+    }
+
+    pairs.sort();
+    pairs
+  }
+}
+
+// every pair, for comparing against the grid
+pub fn get_pairs_brute_force(ids: &[usize]) -> Vec<(usize, usize)> {
+  let mut pairs = vec![];
+
+  for i in 0..ids.len() {
+    for j in i+1..ids.len() {
+      pairs.push((std::cmp::min(ids[i], ids[j]), std::cmp::max(ids[i], ids[j])));
+    }
+  }
+
+  pairs.sort();
+  pairs
+}
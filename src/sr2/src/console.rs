@@ -0,0 +1,201 @@
+use super::*;
+
+// not in the original game, for trying things out without recompiling
+
+// faster would run more steps per frame than can keep up
+const MAX_TIME_SCALE: FScalar = 8.;
+
+const MAX_LINES: usize = 200;
+const BACKGROUND_COLOR: Color = Color { r: 0, g: 0, b: 0, a: 192 };
+const CONSOLE_FONT: FontId = 0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+  Help,
+  // next to the player
+  Spawn(ClassId),
+  Teleport(Vec3f),
+  Level(LevelId),
+  TimeScale(FScalar),
+  // None toggles all of them
+  Overlay(Option<String>),
+  Entities,
+  Entity(usize),
+  List(String)
+}
+
+pub const HELP: &'static [&'static str] = &[
+  "spawn <class>        add an entity next to the player",
+  "teleport <x> <y>     move the player, in pixels",
+  "level <id>           start the level again or switch to another one",
+  "timescale <scale>    speed up or slow down the game, up to 8 times",
+  "overlay [name]       toggle the debug overlay, or a single part of it:",
+  "                     collision tiledata gangdata routes entities trafficlight",
+  "entities             list the entities",
+  "entity <id>          print everything about an entity",
+  "list <table>         list the entries of a table in bin.all:",
+  "                     classes clips sprites images palettes levels weapons vehicles gangs items"
+];
+
+fn parse_arg<T: std::str::FromStr>(args: &[&str], i: usize, name: &str) -> Result<T, String> {
+  match args.get(i) {
+    Some(arg) => arg.parse::<T>().map_err(|_| format!("Invalid {}: {}", name, arg)),
+    None => Err(format!("Missing {}", name))
+  }
+}
+
+pub fn parse_command(line: &str) -> Result<Command, String> {
+  let words: Vec<&str> = line.split_whitespace().collect();
+  let args = if words.len() > 1 { &words[1..] } else { &[] };
+
+  let command = match words.first() {
+    Some(command) => command.to_lowercase(),
+    None => return Err("".to_string())
+  };
+
+  match &command[..] {
+    "help" => Ok(Command::Help),
+    "spawn" => Ok(Command::Spawn(parse_arg(args, 0, "class")?)),
+    "teleport" => Ok(Command::Teleport(Vec3f::new2(parse_arg(args, 0, "x")?, parse_arg(args, 1, "y")?))),
+    "level" => Ok(Command::Level(parse_arg(args, 0, "level")?)),
+    "timescale" => {
+      let scale: FScalar = parse_arg(args, 0, "scale")?;
+      if scale <= 0. || scale > MAX_TIME_SCALE {
+        return Err(format!("The scale has to be above 0 and at most {}", MAX_TIME_SCALE));
+      }
+
+      Ok(Command::TimeScale(scale))
+    },
+    "overlay" => Ok(Command::Overlay(args.first().map(|name| name.to_lowercase()))),
+    "entities" => Ok(Command::Entities),
+    "entity" => Ok(Command::Entity(parse_arg(args, 0, "entity")?)),
+    "list" => Ok(Command::List(parse_arg(args, 0, "table")?)),
+    _ => Err(format!("Unknown command: {}, try help", command))
+  }
+}
+
+fn list_table<T: std::fmt::Debug>(lines: &mut Vec<String>, table: &[T]) {
+  for (i, entry) in table.iter().enumerate() {
+    lines.push(format!("{}: {:?}", i, entry));
+  }
+}
+
+// the entries of one of the tables from bin.all, None if there's no such table
+pub fn list_data(context: &globals::Context, table: &str) -> Option<Vec<String>> {
+  let data = &context.data;
+  let mut lines = vec![];
+
+  match &table.to_lowercase()[..] {
+    "classes" => list_table(&mut lines, &data.classes),
+    "clips" => list_table(&mut lines, &data.clips),
+    "sprites" => {
+      for (i, sprite) in data.sprites.iter().enumerate() {
+        lines.push(format!("{}: {:?} {:?}", i, sprite.aabb, sprite.draw));
+      }
+    },
+    "images" => list_table(&mut lines, &data.images),
+    "palettes" => {
+      for (i, palette) in data.palettes.iter().enumerate() {
+        lines.push(format!("{}: {} colors", i, palette.colors.len()));
+      }
+    },
+    "levels" => list_table(&mut lines, &data.levels),
+    "weapons" => list_table(&mut lines, &data.weapons),
+    "vehicles" => list_table(&mut lines, &data.vehicles),
+    "gangs" => list_table(&mut lines, &data.gangs),
+    "items" => list_table(&mut lines, &data.items),
+    _ => return None
+  }
+
+  Some(lines)
+}
+
+// drops down from the top of the screen, commands are run by the GameScreen
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Console {
+  pub open: bool,
+  pub input: String,
+  pub lines: Vec<String>,
+  // the text typed by the key that opens and closes the console, which comes after the key press
+  skip_text: bool
+}
+
+impl Console {
+  pub fn new() -> Self {
+    Console::default()
+  }
+
+  pub fn print(&mut self, line: &str) {
+    println!("{}", line);
+    // the fonts only have ascii
+    self.lines.push(line.chars().map(|c| if c.is_ascii() { c } else { '?' }).collect());
+
+    if self.lines.len() > MAX_LINES {
+      let extra = self.lines.len() - MAX_LINES;
+      self.lines.drain(..extra);
+    }
+  }
+
+  // has to see every key press, also while closed
+  pub fn watch_key(&mut self, bindings: &bindings::InputBindings, event: Event) {
+    if let Event::Key { pressed: true, key } = event {
+      self.skip_text = key.value != 0 && bindings.lookup_key(key).contains(&input::InputKey::Console);
+    }
+  }
+
+  // returns the line that was entered, if any
+  pub fn input(&mut self, bindings: &bindings::InputBindings, event: Event) -> Option<String> {
+    match event {
+      Event::Key { pressed: true, key } if bindings.lookup_key(key).contains(&input::InputKey::Console) => {
+        self.open = false;
+      },
+      Event::Key { pressed: true, key } => {
+        match key.scancode {
+          bindings::scancodes::ESCAPE => {
+            self.open = false;
+          },
+          bindings::scancodes::BACKSPACE => {
+            self.input.pop();
+          },
+          bindings::scancodes::RETURN => {
+            let line = std::mem::replace(&mut self.input, String::new());
+            self.print(&format!("> {}", line)[..]);
+            return Some(line);
+          },
+          _ => {}
+        }
+      },
+      Event::TextInput(_) if self.skip_text => {
+        self.skip_text = false;
+      },
+      Event::TextInput(character) if character.is_ascii() && !character.is_control() => {
+        self.input.push(character);
+      },
+      _ => {}
+    }
+
+    None
+  }
+
+  pub fn draw(&self, context: &mut globals::Context) {
+    let size = viewport::get_screen_size(context);
+    let height = size.y / 2;
+    let line_height = context.data.fonts[CONSOLE_FONT as usize].height as IScalar;
+
+    context.platform.set_color(BACKGROUND_COLOR);
+    context.platform.fill_rect(0, 0, size.x, height);
+
+    // newest at the bottom, right above the input
+    let mut y = height - line_height * 2;
+    for line in self.lines.iter().rev() {
+      if y < 0 {
+        break;
+      }
+
+      text::draw_text(context, CONSOLE_FONT, line, Vec3i::new2(2, y));
+      y -= line_height;
+    }
+
+    text::draw_text(context, CONSOLE_FONT, &format!("> {}_", self.input)[..], Vec3i::new2(2, height - line_height));
+  }
+}
@@ -1,11 +1,72 @@
 use super::*;
 
 pub trait PlayerController {
-  fn process(&mut self, delta: Time, data: &mut person::PlayerData);
+  fn process(&mut self, context: &mut globals::Context, view: &screen::GameView,
+             player: &mut entity::EntityBase, delta: Time, data: &mut person::PlayerData);
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ControlScheme {
+  // aims with the mouse or the right stick
+  Modern,
+  // keyboard only, like the original game
+  Classic,
+  ClassicTank
+}
+
+impl ControlScheme {
+  pub fn create_controller(&self) -> Box<PlayerController> {
+    match *self {
+      ControlScheme::Modern  => Box::new(ModernPlayerControls::new()),
+      ControlScheme::Classic => Box::new(ClassicPlayerControls::new(ClassicMovement::EightWay)),
+      ControlScheme::ClassicTank => Box::new(ClassicPlayerControls::new(ClassicMovement::Tank))
+    }
+  }
+
+  pub fn next(&self) -> ControlScheme {
+    match *self {
+      ControlScheme::Modern  => ControlScheme::Classic,
+      ControlScheme::Classic => ControlScheme::ClassicTank,
+      ControlScheme::ClassicTank => ControlScheme::Modern
+    }
+  }
 }
 
 pub struct ModernPlayerControls {}
 
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ClassicMovement {
+  // the player faces the direction of the pressed keys
+  EightWay,
+  // left/right turn, up/down move forward/backward
+  Tank
+}
+
+pub struct ClassicPlayerControls {
+  movement: ClassicMovement
+}
+
+// radians per second
+const TANK_TURN_SPEED: Angle = util::PI;
+const AUTO_AIM_RANGE: FScalar = 120.;
+
+fn can_move(player: &entity::EntityBase) -> bool {
+  // TODO implement sliding
+  player.stance == entity::EntityStance::Standing ||
+    player.stance == entity::EntityStance::Aiming ||
+    player.stance == entity::EntityStance::Walking ||
+    player.stance == entity::EntityStance::Running
+}
+
+fn stop_moving(player: &mut entity::EntityBase) {
+  player.speed = 0.;
+
+  if player.stance.is_self_moving() {
+    player.set_new_stance(entity::EntityStance::Standing);
+  }
+}
+
+// relative to the player's angle (RANGLE_*)
 fn get_movement_direction(context: &globals::Context) -> Option<Angle> {
   // TODO: sort by time
 
@@ -59,53 +120,167 @@ impl ModernPlayerControls {
     ModernPlayerControls {}
   }
 
-  fn get_angle_from_mouse(player: &entity::Entity, context: &globals::Context) -> Option<Angle> {
+  fn get_angle_from_mouse(player: &entity::EntityBase, context: &globals::Context, view: &screen::GameView) -> Option<Angle> {
     //let middle_delta = context.input.mouse - (context.platform.get_size() / 2);
-    let game = globals::get_game();
 
     //let mouse_ingame_pos = context.input.mouse - game.main_camera_pos;
-    let mouse_ingame_pos = game.screen_pos_to_game_pos(context.input.mouse);
-    let middle_delta = mouse_ingame_pos - player.base.pos;
+    let mouse_ingame_pos = view.screen_pos_to_game_pos(context.input.mouse);
+    let middle_delta = mouse_ingame_pos - player.pos;
 
-    if middle_delta.len2() < player.base.get_class().width / 2. {
+    if middle_delta.len2() < player.get_class(context).width / 2. {
       None
     } else {
       Some(util::vec_angle(middle_delta / 2.))
     }
   }
+
+  // right stick aims, otherwise face the direction of the left stick
+  fn get_angle_from_controller(context: &globals::Context) -> Option<Angle> {
+    if context.input.right_stick.len2() > 0. {
+      Some(util::vec_angle(context.input.right_stick))
+    } else if context.input.left_stick.len2() > 0. {
+      Some(util::vec_angle(context.input.left_stick))
+    } else {
+      None
+    }
+  }
 }
 
 impl PlayerController for ModernPlayerControls {
-  fn process(&mut self, delta: Time, _data: &mut person::PlayerData) {
-    let context = globals::get_context();
-    let player = &mut globals::get_game().entities[0];
+  fn process(&mut self, context: &mut globals::Context, view: &screen::GameView,
+             player: &mut entity::EntityBase, delta: Time, _data: &mut person::PlayerData) {
+    let angle = if context.input.using_controller {
+      ModernPlayerControls::get_angle_from_controller(context)
+    } else {
+      ModernPlayerControls::get_angle_from_mouse(player, context, view)
+    };
 
-    let angle = ModernPlayerControls::get_angle_from_mouse(player, context);
-    let has_angle = angle.is_some();
+    // a controller keeps the last angle when the sticks are released
+    let has_angle = angle.is_some() || context.input.using_controller;
     if let Some(angle) = angle {
-      player.base.angle = angle;
+      player.angle = angle;
     }
 
-    // TODO implement sliding
-    if (player.base.stance == entity::EntityStance::Standing ||
-        player.base.stance == entity::EntityStance::Aiming ||
-        player.base.stance == entity::EntityStance::Walking ||
-        player.base.stance == entity::EntityStance::Running) {
+    if can_move(player) {
       let mut movement_direction = None;
+      let mut movement_amount = 1.;
       if has_angle {
-        movement_direction = get_movement_direction(context);
+        let left_stick = context.input.left_stick;
+
+        if context.input.using_controller && left_stick.len2() > 0. {
+          // relative to the player's angle, like the keyboard directions
+          movement_direction = Some(util::vec_angle(left_stick) - player.angle);
+          movement_amount = left_stick.len2();
+        } else {
+          movement_direction = get_movement_direction(context);
+        }
       }
 
       if let Some(movement_direction) = movement_direction {
-        player.base.speed = person::PLAYER_SPEED;
-        player.base.set_new_stance(entity::EntityStance::Running);
+        player.speed = person::PLAYER_SPEED * movement_amount;
+        if movement_amount < 0.5 {
+          player.set_new_stance(entity::EntityStance::Walking);
+        } else {
+          player.set_new_stance(entity::EntityStance::Running);
+        }
 
-        player.base.strafe(movement_direction, delta);
+        player.strafe(movement_direction, delta);
       } else {
-        player.base.speed = 0.;
+        stop_moving(player);
+      }
+    }
+  }
+}
+
+impl ClassicPlayerControls {
+  pub fn new(movement: ClassicMovement) -> Self {
+    ClassicPlayerControls {
+      movement
+    }
+  }
+
+  fn find_auto_aim_target(view: &screen::GameView, player: &entity::EntityBase) -> Option<Vec3f> {
+    let mut nearest = None;
+    let mut nearest_distance = AUTO_AIM_RANGE;
+
+    for entity in view.other_entities() {
+      if entity.base.hidden ||
+        entity.base.stance == entity::EntityStance::Dead ||
+        !entity.base.entity_type.is_hostile() {
+          continue;
+        }
+
+      let distance = (entity.base.pos - player.pos).len2();
+      if distance < nearest_distance {
+        nearest_distance = distance;
+        nearest = Some(entity.base.pos);
+      }
+    }
+
+    nearest
+  }
+
+  fn process_eight_way(&self, player: &mut entity::EntityBase, context: &globals::Context, delta: Time) -> bool {
+    if let Some(direction) = get_movement_direction(context) {
+      player.angle = util::normalize_angle(direction + util::angles::ANGLE_N);
+      player.speed = person::PLAYER_SPEED;
+      player.set_new_stance(entity::EntityStance::Running);
+
+      player.move_forward(delta);
+      true
+    } else {
+      false
+    }
+  }
+
+  fn process_tank(&self, player: &mut entity::EntityBase, context: &globals::Context, delta: Time) -> bool {
+    let turn_amount = TANK_TURN_SPEED * (delta as Angle / 1000.);
+
+    if context.input.keys.contains_key(&input::InputKey::Left) {
+      player.angle = util::normalize_angle(player.angle - turn_amount);
+    } else if context.input.keys.contains_key(&input::InputKey::Right) {
+      player.angle = util::normalize_angle(player.angle + turn_amount);
+    }
+
+    if context.input.keys.contains_key(&input::InputKey::Up) {
+      player.speed = person::PLAYER_SPEED;
+      player.set_new_stance(entity::EntityStance::Running);
+      player.strafe(util::angles::RANGLE_N, delta);
+      true
+    } else if context.input.keys.contains_key(&input::InputKey::Down) {
+      // backing up is slower
+      player.speed = person::PLAYER_SPEED / 2.;
+      player.set_new_stance(entity::EntityStance::Walking);
+      player.strafe(util::angles::RANGLE_S, delta);
+      true
+    } else {
+      false
+    }
+  }
+}
+
+impl PlayerController for ClassicPlayerControls {
+  fn process(&mut self, context: &mut globals::Context, view: &screen::GameView,
+             player: &mut entity::EntityBase, delta: Time, _data: &mut person::PlayerData) {
+    if !can_move(player) {
+      return;
+    }
+
+    let moved = match self.movement {
+      ClassicMovement::EightWay => self.process_eight_way(player, context, delta),
+      ClassicMovement::Tank     => self.process_tank(player, context, delta)
+    };
+
+    if !moved {
+      stop_moving(player);
+    }
+
+    if context.input.keys.contains_key(&input::InputKey::Attack) {
+      if let Some(target) = ClassicPlayerControls::find_auto_aim_target(view, player) {
+        player.angle = util::vec_angle(target - player.pos);
 
-        if player.base.stance.is_self_moving() {
-          player.base.set_new_stance(entity::EntityStance::Standing);
+        if !moved {
+          player.set_new_stance(entity::EntityStance::Aiming);
         }
       }
     }
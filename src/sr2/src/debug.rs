@@ -0,0 +1,246 @@
+use super::*;
+
+// not in the original game, shows what the game knows about the level and entities
+
+const WALL_COLOR: Color = Color { r: 255, g: 0, b: 0, a: 96 };
+const SIDEWALK_COLOR: Color = Color { r: 0, g: 128, b: 255, a: 96 };
+const ROAD_COLOR: Color = Color { r: 128, g: 128, b: 128, a: 96 };
+const INTERSECTION_COLOR: Color = Color { r: 255, g: 255, b: 0, a: 96 };
+const OTHER_TILE_COLOR: Color = Color { r: 0, g: 255, b: 0, a: 64 };
+const ROAD_DIRECTION_COLOR: Color = Color { r: 255, g: 255, b: 255, a: 192 };
+const ROUTE_COLOR: Color = Color { r: 255, g: 0, b: 255, a: 255 };
+const COLLISION_COLOR: Color = Color { r: 0, g: 255, b: 0, a: 255 };
+const GANG_COLORS: [Color; 4] = [
+  Color { r: 255, g: 0, b: 128, a: 64 },
+  Color { r: 128, g: 0, b: 255, a: 64 },
+  Color { r: 255, g: 128, b: 0, a: 64 },
+  Color { r: 0, g: 255, b: 255, a: 64 }
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct DebugOverlay {
+  pub collision: bool,
+  pub tiledata: bool,
+  pub gangdata: bool,
+  pub routes: bool,
+  pub entities: bool,
+  pub trafficlight: bool
+}
+
+impl DebugOverlay {
+  pub fn new() -> Self {
+    DebugOverlay::default()
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    self.collision || self.tiledata || self.gangdata || self.routes || self.entities || self.trafficlight
+  }
+
+  // returns false if there's no such part
+  pub fn toggle_part(&mut self, name: &str) -> bool {
+    let part = match name {
+      "collision" => &mut self.collision,
+      "tiledata" => &mut self.tiledata,
+      "gangdata" => &mut self.gangdata,
+      "routes" => &mut self.routes,
+      "entities" => &mut self.entities,
+      "trafficlight" => &mut self.trafficlight,
+      _ => return false
+    };
+
+    *part = !*part;
+    true
+  }
+
+  // everything on, or everything off if anything was on
+  pub fn toggle(&mut self) {
+    let enabled = !self.is_enabled();
+
+    *self = DebugOverlay {
+      collision: enabled,
+      tiledata: enabled,
+      gangdata: enabled,
+      routes: enabled,
+      entities: enabled,
+      trafficlight: enabled
+    };
+  }
+}
+
+fn get_tile_color(tiledata: LevelTileData) -> Option<Color> {
+  if level::tiledata_is_impassable(tiledata) {
+    Some(WALL_COLOR)
+  } else if level::tiledata_is_sidewalk(tiledata) {
+    Some(SIDEWALK_COLOR)
+  } else if vehicle::is_intersection(tiledata) {
+    Some(INTERSECTION_COLOR)
+  } else if vehicle::is_road(tiledata) {
+    Some(ROAD_COLOR)
+  } else if tiledata != 0 {
+    Some(OTHER_TILE_COLOR)
+  } else {
+    None
+  }
+}
+
+// tiles on the screen, as start and end
+fn get_visible_tiles(context: &globals::Context, level: &Level) -> (Vec3i, Vec3i) {
+  let view = render::get_view_aabb(context);
+
+  let start = Vec3i::new2(std::cmp::max(0, view[0] / util::TILESIZE),
+                          std::cmp::max(0, view[1] / util::TILESIZE));
+  let end = Vec3i::new2(std::cmp::min(level.tiledata_size.x, view[2] / util::TILESIZE + 1),
+                        std::cmp::min(level.tiledata_size.y, view[3] / util::TILESIZE + 1));
+
+  (start, end)
+}
+
+fn draw_tiledata(context: &mut globals::Context, level: &Level, trafficlight: vehicle::TrafficLight) {
+  let (start, end) = get_visible_tiles(context, level);
+  let half = util::TILESIZE / 2;
+
+  for y in start.y..end.y {
+    for x in start.x..end.x {
+      let tiledata = level.tiledata[(y * level.tiledata_size.x + x) as usize];
+      let pos = Vec3i::new2(x * util::TILESIZE, y * util::TILESIZE);
+
+      if let Some(color) = get_tile_color(tiledata) {
+        context.platform.set_color(color);
+        context.platform.fill_rect(pos.x, pos.y, util::TILESIZE, util::TILESIZE);
+      }
+
+      // the way traffic goes right now
+      if let Some(angle) = vehicle::get_road_direction(tiledata, trafficlight) {
+        let middle = pos + Vec3i::new2(half, half);
+        let end = middle + Vec3i::from(util::cossin(angle) * (half - 2) as FScalar);
+
+        context.platform.set_color(ROAD_DIRECTION_COLOR);
+        context.platform.draw_line(middle.x, middle.y, end.x, end.y);
+        context.platform.fill_rect(end.x - 1, end.y - 1, 3, 3);
+      }
+    }
+  }
+}
+
+fn draw_gangdata(context: &mut globals::Context, level: &Level) {
+  if level.tile_gangdata.is_empty() {
+    return;
+  }
+
+  let (start, end) = get_visible_tiles(context, level);
+
+  for y in start.y..end.y {
+    for x in start.x..end.x {
+      let gang = level.tile_gangdata[(y * level.tiledata_size.x + x) as usize];
+      if gang <= 0 {
+        continue;
+      }
+
+      context.platform.set_color(GANG_COLORS[(gang - 1) as usize % GANG_COLORS.len()]);
+      context.platform.fill_rect(x * util::TILESIZE, y * util::TILESIZE, util::TILESIZE, util::TILESIZE);
+    }
+  }
+}
+
+fn draw_routes(context: &mut globals::Context, level: &Level) {
+  for (i, route) in level.routes.iter().enumerate() {
+    context.platform.set_color(ROUTE_COLOR);
+    for pair in route.parts.windows(2) {
+      let from = Vec3i::from(pair[0].pos);
+      let to = Vec3i::from(pair[1].pos);
+      context.platform.draw_line(from.x, from.y, to.x, to.y);
+    }
+
+    for (j, part) in route.parts.iter().enumerate() {
+      let pos = Vec3i::from(part.pos);
+
+      context.platform.set_color(ROUTE_COLOR);
+      context.platform.fill_rect(pos.x - 1, pos.y - 1, 3, 3);
+      text::draw_text(context, 0, &format!("{}.{}", i, j)[..], pos + Vec3i::new2(2, 2));
+    }
+  }
+}
+
+fn draw_collision(context: &mut globals::Context, entities: &[entity::Entity]) {
+  context.platform.set_color(COLLISION_COLOR);
+
+  for entity in entities.iter() {
+    let collision = match entity.collision {
+      Some(ref collision) if entity.is_physical() => collision,
+      _ => continue
+    };
+
+    let pos = Vec3i::new2(collision.isometry.translation.vector.x as IScalar,
+                          collision.isometry.translation.vector.y as IScalar);
+
+    match collision.shape {
+      collision::Shape::Circle(radius) => {
+        context.platform.draw_arc(pos.x - radius, pos.y - radius, radius * 2, radius * 2, 0, 360);
+      },
+      // half the size in each direction, rotated with the entity
+      collision::Shape::Rect(half) => {
+        let corners: Vec<Vec3i> = [(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)].iter().map(|&(x, y)| {
+          let point = collision.isometry * nalgebra::Point2::new(half.x as FScalar * x, half.y as FScalar * y);
+          Vec3i::new2(point.x as IScalar, point.y as IScalar)
+        }).collect();
+
+        for i in 0..corners.len() {
+          let from = corners[i];
+          let to = corners[(i + 1) % corners.len()];
+          context.platform.draw_line(from.x, from.y, to.x, to.y);
+        }
+      }
+    }
+  }
+}
+
+fn draw_entities(context: &mut globals::Context, entities: &[entity::Entity]) {
+  for (i, entity) in entities.iter().enumerate() {
+    if entity.base.hidden {
+      continue;
+    }
+
+    let pos = Vec3i::from(entity.base.get_draw_pos(context));
+    let label = format!("{} {:?} {:?}", i, entity.base.entity_type, entity.base.stance);
+    text::draw_text(context, 0, &label[..], pos);
+  }
+}
+
+fn draw_trafficlight(context: &mut globals::Context, trafficlight: vehicle::TrafficLight) {
+  text::draw_text(context, 0, &format!("Traffic light: {:?}", trafficlight)[..], Vec3i::new2(4, 4));
+}
+
+// the level and entity parts go on top of the world, the traffic light is on the screen
+pub fn queue_overlay<'a>(queue: &mut render::RenderQueue<'a>, overlay: DebugOverlay, level: &'a Level, entities: &'a [entity::Entity], trafficlight: vehicle::TrafficLight) {
+  if !overlay.is_enabled() {
+    return;
+  }
+
+  queue.push(render::Layer::Effects, render::Drawable::Custom(Box::new(move |context: &mut globals::Context| {
+    if overlay.tiledata {
+      draw_tiledata(context, level, trafficlight);
+    }
+
+    if overlay.gangdata {
+      draw_gangdata(context, level);
+    }
+
+    if overlay.routes {
+      draw_routes(context, level);
+    }
+
+    if overlay.collision {
+      draw_collision(context, entities);
+    }
+
+    if overlay.entities {
+      draw_entities(context, entities);
+    }
+  })));
+
+  if overlay.trafficlight {
+    queue.push(render::Layer::Hud, render::Drawable::Custom(Box::new(move |context: &mut globals::Context| {
+      draw_trafficlight(context, trafficlight);
+    })));
+  }
+}
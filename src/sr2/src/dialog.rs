@@ -16,12 +16,14 @@ pub enum SizeBoundary {
 }
 
 pub trait Widget {
-  fn input(&mut self, _event: Event) {}
-  fn set_boundaries(&mut self, _size: SizeBoundary) {}
-  fn get_size(&self) -> Vec3i { Vec3i::default() }
-  fn get_min_size(&self) -> Option<Vec3i> { None }
-  fn get_max_size(&self) -> Option<Vec3i> { None }
-  fn draw(&self, _offset: Vec3i) {}
+  // returns true if the event was consumed
+  fn input(&mut self, _context: &mut globals::Context, _event: Event) -> bool { false }
+  fn is_closed(&self) -> bool { false }
+  fn set_boundaries(&mut self, _context: &globals::Context, _size: SizeBoundary) {}
+  fn get_size(&self, _context: &globals::Context) -> Vec3i { Vec3i::default() }
+  fn get_min_size(&self, _context: &globals::Context) -> Option<Vec3i> { None }
+  fn get_max_size(&self, _context: &globals::Context) -> Option<Vec3i> { None }
+  fn draw(&self, _context: &mut globals::Context, _offset: Vec3i) {}
 }
 
 const DIALOG_MARGIN: IScalar = 20;
@@ -37,8 +39,8 @@ pub struct Dialog {
 }
 
 impl Dialog {
-  pub fn new(widget: Box<Widget>) -> Self {
-    let size = widget.get_size() + DIALOG_PADDING * 2;
+  pub fn new(context: &globals::Context, widget: Box<Widget>) -> Self {
+    let size = widget.get_size(context) + DIALOG_PADDING * 2;
 
     Dialog {
       boundaries: SizeBoundary::None,
@@ -50,12 +52,20 @@ impl Dialog {
 }
 
 impl Widget for Dialog {
-  fn set_boundaries(&mut self, new_boundaries: SizeBoundary) {
+  fn input(&mut self, context: &mut globals::Context, event: Event) -> bool {
+    self.widget.input(context, event)
+  }
+
+  fn is_closed(&self) -> bool {
+    self.widget.is_closed()
+  }
+
+  fn set_boundaries(&mut self, context: &globals::Context, new_boundaries: SizeBoundary) {
     if new_boundaries == self.boundaries {
       return;
     }
 
-    let size = self.get_size();
+    let size = self.get_size(context);
 
     self.boundaries = new_boundaries;
 
@@ -70,25 +80,23 @@ impl Widget for Dialog {
         boundary.y = boundary.y - DIALOG_MARGIN2;
       }
 
-      self.widget.set_boundaries(SizeBoundary::Both(boundary - DIALOG_PADDING * 2));
+      self.widget.set_boundaries(context, SizeBoundary::Both(boundary - DIALOG_PADDING * 2));
     } else {
       // A dialog should always be top-level
       panic!("Dialog.set_boundaries() should be SizeBoundary::Both");
     }
   }
 
-  fn get_size(&self) -> Vec3i {
-    self.widget.get_size() + DIALOG_PADDING * 2
+  fn get_size(&self, context: &globals::Context) -> Vec3i {
+    self.widget.get_size(context) + DIALOG_PADDING * 2
   }
 
-  fn get_min_size(&self) -> Option<Vec3i> {
-    Some(Vec3i::new2(DIALOG_MARGIN2, DIALOG_MARGIN2) + self.widget.get_min_size().unwrap_or(Vec3i::default()))
+  fn get_min_size(&self, context: &globals::Context) -> Option<Vec3i> {
+    Some(Vec3i::new2(DIALOG_MARGIN2, DIALOG_MARGIN2) + self.widget.get_min_size(context).unwrap_or(Vec3i::default()))
   }
 
-  fn draw(&self, offset: Vec3i) {
-    let context = globals::get_context();
-
-    let size = self.get_size();
+  fn draw(&self, context: &mut globals::Context, offset: Vec3i) {
+    let size = self.get_size(context);
     let offset = offset + (self.boundaries_size - size) / 2;
 
     context.platform.set_color(Color { r: 0, g: 0, b: 0, a: 150 });
@@ -96,7 +104,7 @@ impl Widget for Dialog {
                                size.x,
                                size.y);
 
-    self.widget.draw(offset + DIALOG_PADDING);
+    self.widget.draw(context, offset + DIALOG_PADDING);
   }
 }
 
@@ -110,7 +118,7 @@ pub struct TextWidget {
 }
 
 impl TextWidget {
-  pub fn new(text: &str) -> Self {
+  pub fn new(context: &globals::Context, text: &str) -> Self {
     let mut widget = TextWidget {
       boundaries: SizeBoundary::None,
       text: text.to_string(),
@@ -120,16 +128,16 @@ impl TextWidget {
       actual_size: Vec3i::default()
     };
 
-    widget.set_text(text);
+    widget.set_text(context, text);
 
     widget
   }
 
-  fn get_split_size(lines: &Vec<String>) -> Vec3i {
+  fn get_split_size(context: &globals::Context, lines: &Vec<String>) -> Vec3i {
     let mut size = Vec3i::default();
 
     for line in lines.iter() {
-      let line_size = text::text_size(0, &line[..]);
+      let line_size = text::text_size(context, 0, &line[..]);
       if line_size.x > size.x {
         size.x = line_size.x;
       }
@@ -140,7 +148,7 @@ impl TextWidget {
     size
   }
 
-  fn update_split_text(&mut self) {
+  fn update_split_text(&mut self, context: &globals::Context) {
     self.split_text = vec![];
 
     let mut size = Vec3i::default();
@@ -149,10 +157,10 @@ impl TextWidget {
       self.split_text.push(line.to_string());
     }
 
-    self.full_size = TextWidget::get_split_size(&self.split_text);
+    self.full_size = TextWidget::get_split_size(context, &self.split_text);
   }
 
-  fn update_wrap(&mut self) {
+  fn update_wrap(&mut self, context: &globals::Context) {
     let x_size = match self.boundaries {
       SizeBoundary::Both(size) => { size.x },
       SizeBoundary::X(x) => { x },
@@ -164,38 +172,38 @@ impl TextWidget {
       }
     };
 
-    self.wrapped = text::word_wrap(0, &self.text[..], x_size);
-    self.actual_size = TextWidget::get_split_size(&self.wrapped);
+    self.wrapped = text::word_wrap(context, 0, &self.text[..], x_size);
+    self.actual_size = TextWidget::get_split_size(context, &self.wrapped);
   }
 
-  pub fn set_text(&mut self, text: &str) {
+  pub fn set_text(&mut self, context: &globals::Context, text: &str) {
     self.text = text.to_string();
 
-    self.update_split_text();
-    self.update_wrap();
+    self.update_split_text(context);
+    self.update_wrap(context);
   }
 }
 
 impl Widget for TextWidget {
-  fn set_boundaries(&mut self, new_boundaries: SizeBoundary) {
+  fn set_boundaries(&mut self, context: &globals::Context, new_boundaries: SizeBoundary) {
     self.boundaries = new_boundaries;
-    self.update_wrap();
+    self.update_wrap(context);
   }
 
-  fn get_size(&self) -> Vec3i {
+  fn get_size(&self, _context: &globals::Context) -> Vec3i {
     self.actual_size
   }
 
-  fn get_max_size(&self) -> Option<Vec3i> {
+  fn get_max_size(&self, _context: &globals::Context) -> Option<Vec3i> {
     Some(self.full_size)
   }
 
-  fn draw(&self, offset: Vec3i) {
+  fn draw(&self, context: &mut globals::Context, offset: Vec3i) {
     // TODO: clipping, alignment
     let mut offset = offset;
 
     for line in self.wrapped.iter() {
-      let size = text::draw_text(0, line, offset);
+      let size = text::draw_text(context, 0, line, offset);
       offset.y += size.y;
     }
   }
@@ -297,9 +305,9 @@ impl BoxContainer {
     }
   }
 
-  fn quick_set_boundaries(&mut self) {
+  fn quick_set_boundaries(&mut self, context: &globals::Context) {
     for item in self.items.iter_mut() {
-      item.widget.set_boundaries(self.boundaries);
+      item.widget.set_boundaries(context, self.boundaries);
     }
   }
 }
@@ -307,7 +315,7 @@ impl BoxContainer {
 impl Widget for BoxContainer {
   // TODO: optimize duplicate get_size() calls
 
-  fn set_boundaries(&mut self, new_boundaries: SizeBoundary) {
+  fn set_boundaries(&mut self, context: &globals::Context, new_boundaries: SizeBoundary) {
     // TODO: fix padding
     if new_boundaries == self.boundaries {
       return;
@@ -317,18 +325,18 @@ impl Widget for BoxContainer {
 
     let mut remaining_size = match new_boundaries {
       SizeBoundary::None => {
-        return self.quick_set_boundaries();
+        return self.quick_set_boundaries(context);
       },
       SizeBoundary::X(x) => {
         if self.orientation == BoxOrientation::VERTICAL {
-          return self.quick_set_boundaries();
+          return self.quick_set_boundaries(context);
         }
 
         x
       },
       SizeBoundary::Y(y) => {
         if self.orientation == BoxOrientation::HORIZONTAL {
-          return self.quick_set_boundaries();
+          return self.quick_set_boundaries(context);
         }
 
         y
@@ -372,9 +380,9 @@ impl Widget for BoxContainer {
         }
       };
 
-      item.widget.set_boundaries(item_boundaries);
+      item.widget.set_boundaries(context, item_boundaries);
 
-      let item_size = item.get_full_size(item.widget.get_size());
+      let item_size = item.get_full_size(item.widget.get_size(context));
       remaining_size -= if self.orientation == BoxOrientation::VERTICAL {
         item_size.y
       } else {
@@ -385,11 +393,11 @@ impl Widget for BoxContainer {
     }
   }
 
-  fn get_size(&self) -> Vec3i {
+  fn get_size(&self, context: &globals::Context) -> Vec3i {
     let mut size = Vec3i::default();
 
     for item in self.items.iter() {
-      let item_size = item.widget.get_size();
+      let item_size = item.widget.get_size(context);
       let full_item_size = item.get_full_size(item_size);
 
       size = size + self.calc_size_increase(size, full_item_size);
@@ -398,11 +406,11 @@ impl Widget for BoxContainer {
     size
   }
 
-  fn get_max_size(&self) -> Option<Vec3i> {
+  fn get_max_size(&self, context: &globals::Context) -> Option<Vec3i> {
     let mut size = Vec3i::default();
 
     for item in self.items.iter() {
-      let item_size = item.widget.get_max_size();
+      let item_size = item.widget.get_max_size(context);
 
       match item_size {
         Some(item_size) => {
@@ -419,11 +427,11 @@ impl Widget for BoxContainer {
     Some(size)
   }
 
-  fn get_min_size(&self) -> Option<Vec3i> {
+  fn get_min_size(&self, context: &globals::Context) -> Option<Vec3i> {
     let mut size = Vec3i::default();
 
     for item in self.items.iter() {
-      let item_size = item.widget.get_max_size().unwrap_or(Vec3i::default());
+      let item_size = item.widget.get_max_size(context).unwrap_or(Vec3i::default());
       let full_item_size = item.get_full_size(item_size);
 
       size = size + self.calc_size_increase(size, full_item_size);
@@ -432,11 +440,11 @@ impl Widget for BoxContainer {
     Some(size)
   }
 
-  fn draw(&self, base_offset: Vec3i) {
+  fn draw(&self, context: &mut globals::Context, base_offset: Vec3i) {
     let mut offset = Vec3i::default();
 
     for item in self.items.iter() {
-      let item_size = item.widget.get_size();
+      let item_size = item.widget.get_size(context);
       let mut append_size = Vec3i::default();
 
       match self.orientation {
@@ -454,7 +462,7 @@ impl Widget for BoxContainer {
         }
       }
 
-      item.widget.draw(base_offset + offset);
+      item.widget.draw(context, base_offset + offset);
 
       offset = offset + append_size;
     }
@@ -466,35 +474,180 @@ pub struct PauseMenu {
 }
 
 impl PauseMenu {
-  pub fn new() -> Self {
+  pub fn new(context: &globals::Context) -> Self {
     let mut pausemenu = PauseMenu {
       boxc: BoxContainer::new(BoxOrientation::VERTICAL)
     };
 
-    pausemenu.boxc.add_item_ap(Box::new(TextWidget::new("The quick  brown fox")), 0);
-    pausemenu.boxc.add_item_ap(Box::new(TextWidget::new("jumps")), 30);
-    pausemenu.boxc.add_item_ap(Box::new(TextWidget::new("over the lazy dog. The quick brown fox jumps over the lazy dog. The quick brown fox jumps over the lazy dog.")), 0);
+    pausemenu.boxc.add_item_ap(Box::new(TextWidget::new(context, "The quick  brown fox")), 0);
+    pausemenu.boxc.add_item_ap(Box::new(TextWidget::new(context, "jumps")), 30);
+    pausemenu.boxc.add_item_ap(Box::new(TextWidget::new(context, "over the lazy dog. The quick brown fox jumps over the lazy dog. The quick brown fox jumps over the lazy dog.")), 0);
 
-    pausemenu.boxc.set_boundaries(SizeBoundary::X(50));
+    pausemenu.boxc.set_boundaries(context, SizeBoundary::X(50));
 
     pausemenu
   }
 }
 
 impl Widget for PauseMenu {
-  fn get_size(&self) -> Vec3i {
-    self.boxc.get_size()
+  fn get_size(&self, context: &globals::Context) -> Vec3i {
+    self.boxc.get_size(context)
+  }
+
+  fn get_max_size(&self, context: &globals::Context) -> Option<Vec3i> {
+    self.boxc.get_max_size(context)
+  }
+
+  fn get_min_size(&self, context: &globals::Context) -> Option<Vec3i> {
+    self.boxc.get_min_size(context)
+  }
+
+  fn draw(&self, context: &mut globals::Context, offset: Vec3i) {
+    self.boxc.draw(context, offset);
+  }
+}
+
+pub struct BindingsMenu {
+  selected: usize,
+  capturing: bool,
+  closed: bool
+}
+
+impl BindingsMenu {
+  pub fn new() -> Self {
+    BindingsMenu {
+      selected: 0,
+      capturing: false,
+      closed: false
+    }
+  }
+
+  fn get_lines(&self, context: &globals::Context) -> Vec<String> {
+    let mut lines = vec![];
+
+    lines.push("Controls".to_string());
+
+    for (i, key) in input::InputKey::all().iter().enumerate() {
+      let bindings: Vec<String> = context.input.bindings.get(*key).iter().map(|binding| binding.display_name()).collect();
+
+      let mut line = format!("{}: {}", key.name(), bindings.join(", "));
+      if self.capturing && i == self.selected {
+        line += " ...";
+      }
+
+      lines.push(line);
+    }
+
+    lines.push("Enter/A: add, Backspace: clear, Esc/B: close".to_string());
+
+    lines
+  }
+
+  fn close(&mut self, context: &globals::Context) {
+    if let Err(error) = context.input.bindings.save() {
+      println!("Unable to save input bindings: {}", error);
+    }
+
+    self.closed = true;
   }
+}
+
+impl Widget for BindingsMenu {
+  fn input(&mut self, context: &mut globals::Context, event: Event) -> bool {
+    let selected_key = input::InputKey::all()[self.selected];
 
-  fn get_max_size(&self) -> Option<Vec3i> {
-    self.boxc.get_max_size()
+    if self.capturing {
+      if let Some(binding) = bindings::Binding::from_event(event) {
+        if binding != bindings::Binding::Scancode(bindings::scancodes::ESCAPE) &&
+          binding != bindings::Binding::Controller(ControllerButton::B) {
+          context.input.bindings.add(selected_key, binding);
+        }
+
+        self.capturing = false;
+      }
+
+      return true;
+    }
+
+    match event {
+      Event::Key { pressed: true, key } => {
+        match key.scancode {
+          bindings::scancodes::UP => {
+            if self.selected > 0 {
+              self.selected -= 1;
+            }
+          },
+          bindings::scancodes::DOWN => {
+            if self.selected + 1 < input::InputKey::all().len() {
+              self.selected += 1;
+            }
+          },
+          bindings::scancodes::RETURN => {
+            self.capturing = true;
+          },
+          bindings::scancodes::BACKSPACE => {
+            context.input.bindings.clear(selected_key);
+          },
+          bindings::scancodes::ESCAPE => {
+            self.close(context);
+          },
+          _ => {}
+        }
+
+        true
+      },
+      Event::ControllerButton { pressed: true, button, .. } => {
+        match button {
+          ControllerButton::DPadUp => {
+            if self.selected > 0 {
+              self.selected -= 1;
+            }
+          },
+          ControllerButton::DPadDown => {
+            if self.selected + 1 < input::InputKey::all().len() {
+              self.selected += 1;
+            }
+          },
+          ControllerButton::A => {
+            self.capturing = true;
+          },
+          ControllerButton::B => {
+            self.close(context);
+          },
+          _ => {}
+        }
+
+        true
+      },
+      Event::Key { .. } => true,
+      Event::MouseButton { .. } => true,
+      Event::ControllerButton { .. } => true,
+      _ => false
+    }
+  }
+
+  fn is_closed(&self) -> bool {
+    self.closed
   }
 
-  fn get_min_size(&self) -> Option<Vec3i> {
-    self.boxc.get_min_size()
+  fn get_size(&self, context: &globals::Context) -> Vec3i {
+    TextWidget::get_split_size(context, &self.get_lines(context))
   }
 
-  fn draw(&self, offset: Vec3i) {
-    self.boxc.draw(offset);
+  fn draw(&self, context: &mut globals::Context, offset: Vec3i) {
+    let mut offset = offset;
+
+    for (i, line) in self.get_lines(context).iter().enumerate() {
+      let size = text::text_size(context, 0, line);
+
+      // first line is the title
+      if i == self.selected + 1 {
+        context.platform.set_color(Color { r: 255, g: 255, b: 255, a: 60 });
+        context.platform.fill_rect(offset.x, offset.y, size.x, size.y);
+      }
+
+      text::draw_text(context, 0, line, offset);
+      offset.y += size.y;
+    }
   }
 }
@@ -64,6 +64,12 @@ impl EntityType {
       *self == EntityType::EnemyVehicle ||
       *self == EntityType::PoliceCar;
   }
+
+  pub fn is_hostile(&self) -> bool {
+    *self == EntityType::Gangster ||
+      *self == EntityType::Type7 ||
+      *self == EntityType::EnemyVehicle
+  }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -99,6 +105,23 @@ impl EntityStance {
   }
 }
 
+pub fn get_entitystance(number: i32) -> Option<EntityStance> {
+  match number {
+    0 => Some(EntityStance::Standing),
+    1 => Some(EntityStance::Walking),
+    2 => Some(EntityStance::Running),
+    3 => Some(EntityStance::Dead),
+    4 => Some(EntityStance::LyingDown),
+    5 => Some(EntityStance::Aiming),
+    6 => Some(EntityStance::Shooting),
+    7 => Some(EntityStance::Punching),
+    8 => Some(EntityStance::Riding),
+    9 => Some(EntityStance::Sliding),
+    10 => Some(EntityStance::Unknown),
+    _ => None
+  }
+}
+
 pub fn get_entitytype(number: i32) -> EntityType {
   match number {
     1 => EntityType::Type1,
@@ -153,6 +176,7 @@ pub struct EntityBase {
   pub prev_angle: Angle,
   pub speed: FScalar,
   pub sort_order: IScalar,
+  pub health: IScalar,
 
   pub stance: EntityStance,
   pub stance_millis: Time,
@@ -161,22 +185,24 @@ pub struct EntityBase {
   pub gender: EntityGender,
 
   pub route: route::RouteData,
+  pub animation: animation::AnimationController,
 
   pub hidden: bool,          //    0x01
+  pub broken: bool,          //    0x02
+  pub frozen: bool,          //  0x4000
   pub following_route: bool, // 0x10000
   pub can_update_sort: bool, // 0x20000
 }
 
 impl EntityBase {
-  pub fn new(id: EntityId, class: ClassId) -> Self {
-    let context = globals::get_context();
-
+  pub fn new(context: &globals::Context, id: EntityId, class: ClassId) -> Self {
     EntityBase {
       id,
       class,
       entity_type: get_entitytype(context.data.classes[class as usize].entity_type),
       pos: Vec3f::new2(0., 0.),
       sort_order: 0,
+      health: context.data.classes[class as usize].health as IScalar,
       angle: 0.,
       prev_pos: Vec3f::new2(0., 0.),
       prev_angle: 0.,
@@ -186,17 +212,20 @@ impl EntityBase {
       gender: EntityGender::Female,
 
       route: route::RouteData::default(),
+      animation: animation::AnimationController::new(),
 
       speed: 0.,
 
       hidden: false,
+      broken: false,
+      frozen: false,
       following_route: false,
       can_update_sort: true
     }
   }
 
-  pub fn init(&mut self) {
-    *self = EntityBase::new(self.id, self.class);
+  pub fn init(&mut self, context: &globals::Context) {
+    *self = EntityBase::new(context, self.id, self.class);
   }
 
   pub fn update_prev(&mut self) {
@@ -204,6 +233,15 @@ impl EntityBase {
     self.prev_angle = self.angle;
   }
 
+  // position between the last two simulation steps, for drawing
+  pub fn get_draw_pos(&self, context: &globals::Context) -> Vec3f {
+    self.prev_pos + (self.pos - self.prev_pos) * context.alpha
+  }
+
+  pub fn get_draw_angle(&self, context: &globals::Context) -> Angle {
+    util::interpolate_angle(self.prev_angle, self.angle, context.alpha)
+  }
+
   pub fn strafe(&mut self, angle: Angle, delta: Time) {
     if self.speed == 0. {
       return;
@@ -220,8 +258,8 @@ impl EntityBase {
     self.strafe(0., delta);
   }
 
-  pub fn get_class(&self) -> &EntityClass {
-    &globals::get_context().data.classes[self.class as usize]
+  pub fn get_class<'a>(&self, context: &'a globals::Context) -> &'a EntityClass {
+    &context.data.classes[self.class as usize]
   }
 
   pub fn set_new_stance(&mut self, newstance: EntityStance) {
@@ -233,15 +271,15 @@ impl EntityBase {
     self.stance_millis = 0;
   }
 
-  pub fn pick_gender() -> EntityGender {
-    match util::pick_int(2) {
+  pub fn pick_gender(context: &mut globals::Context) -> EntityGender {
+    match util::pick_cosmetic_int(context, 2) {
       0 => EntityGender::Male,
       _ => EntityGender::Female
     }
   }
 
-  pub fn pick_npc_person_palette() -> PaletteId {
-    12 + util::pick_int(1)
+  pub fn pick_npc_person_palette(context: &mut globals::Context) -> PaletteId {
+    12 + util::pick_cosmetic_int(context, 1)
   }
 
   pub fn update_pos(&mut self) {
@@ -251,6 +289,10 @@ impl EntityBase {
   }
 
   pub fn step_route(&mut self, delta: Time) {
+    if self.frozen {
+      return;
+    }
+
     if self.route.route.is_some() {
       if let Some((pos, angle)) = self.route.step(self.speed, delta) {
         //println!("{:?} {:?}", pos, angle);
@@ -260,17 +302,50 @@ impl EntityBase {
       } else {
         self.following_route = false;
       }
+
+      self.frozen = self.route.waiting_for.is_some();
+
+      let stance = self.route.events.iter().filter_map(|event| match *event {
+        route::RouteEvent::Stance(stance) => Some(stance),
+        _ => None
+      }).last();
+
+      if let Some(stance) = stance {
+        self.set_new_stance(stance);
+      }
+    }
+  }
+
+  // starts following the route the level placed the entity on
+  pub fn follow_level_route(&mut self, level: &Level, speed: FScalar) -> bool {
+    if !self.route.set_route_to_routeid(level) {
+      return false;
     }
+
+    self.following_route = true;
+    self.frozen = false;
+    self.speed = speed;
+    self.set_new_stance(EntityStance::Running);
+    true
   }
 }
 
+// `view` is the rest of the game, without the entity itself
 pub trait EntityData {
-  fn init(&mut self, _entity: &mut EntityBase) {}
-  fn get_collision_info(&self, _entity: &EntityBase) -> Option<collision::ShapeInfo> { None }
-  fn spawn(&mut self, _entity: &mut EntityBase, _pos: Vec3f) -> Option<Vec3f> { None }
-  fn step(&mut self, _entity: &mut EntityBase, _delta: Time) {}
-  fn draw(&self, _entity: &EntityBase) {}
+  fn init(&mut self, _context: &mut globals::Context, _entity: &mut EntityBase) {}
+  fn get_collision_info(&self, _context: &globals::Context, _entity: &EntityBase) -> Option<collision::ShapeInfo> { None }
+  fn spawn(&mut self, _context: &mut globals::Context, _view: &screen::GameView, _entity: &mut EntityBase, _pos: Vec3f) -> Option<Vec3f> { None }
+  fn step(&mut self, _context: &mut globals::Context, _view: &mut screen::GameView, _entity: &mut EntityBase, _delta: Time) {}
+  fn draw(&self, _context: &mut globals::Context, _entity: &EntityBase) {}
   fn despawn_action(&mut self, _entity: &mut EntityBase) -> bool { true }
+  // push is how far the entity was moved out of whatever it collided with,
+  // impact is how much that changed its velocity, in units per second
+  fn on_collision(&mut self, _entity: &mut EntityBase, _push: Vec3f, _impact: Vec3f) {}
+  fn damage(&mut self, _context: &globals::Context, _entity: &mut EntityBase, _amount: IScalar) {}
+  // after the route itself handled the event, stances have been set already
+  fn on_route_event(&mut self, _context: &mut globals::Context, _view: &mut screen::GameView, _entity: &mut EntityBase, _event: route::RouteEvent) {}
+  // a one-shot animation like a punch played to the end
+  fn on_animation_finished(&mut self, _context: &mut globals::Context, _entity: &mut EntityBase) {}
 }
 
 struct NullEntityData();
@@ -293,8 +368,8 @@ fn create_entity_data(entity_type: EntityType) -> Box<EntityData> {
 }
 
 impl Entity {
-  pub fn new(id: EntityId, class: ClassId) -> Self {
-    let base = EntityBase::new(id, class);
+  pub fn new(context: &mut globals::Context, id: EntityId, class: ClassId) -> Self {
+    let base = EntityBase::new(context, id, class);
     let data = create_entity_data(base.entity_type);
 
     let mut entity = Entity {
@@ -303,44 +378,48 @@ impl Entity {
       data
     };
 
-    entity.data.init(&mut entity.base);
+    entity.data.init(context, &mut entity.base);
 
     entity
   }
 
-  pub fn init(&mut self) {
-    self.base.init();
-    self.data.init(&mut self.base);
+  pub fn init(&mut self, context: &mut globals::Context) {
+    self.base.init(context);
+    self.data.init(context, &mut self.base);
   }
 
-  pub fn after_init(&mut self) {
-    if let Some(info) = self.data.get_collision_info(&self.base) {
+  pub fn after_init(&mut self, context: &globals::Context) {
+    if let Some(info) = self.data.get_collision_info(context, &self.base) {
       self.collision = Some(collision::PhysicalObject::new_from_info(info));
     }
+
+    // so it's drawn before its first step
+    let animation = animation::get_animation(context, &self.base);
+    self.base.animation.step(animation, 0);
   }
 
-  pub fn spawn(&mut self, pos: Vec3f) -> Option<Vec3f> {
-    self.init();
+  pub fn spawn(&mut self, context: &mut globals::Context, view: &screen::GameView, pos: Vec3f) -> Option<Vec3f> {
+    self.init(context);
 
-    if let Some(pos) = self.data.spawn(&mut self.base, pos) {
-      self.set_pos(pos);
-      self.after_init();
+    if let Some(pos) = self.data.spawn(context, view, &mut self.base, pos) {
+      self.teleport(pos);
+      self.after_init(context);
       Some(pos)
     } else {
       None
     }
   }
 
-  pub fn get_class(&self) -> &EntityClass {
-    &globals::get_context().data.classes[self.base.class as usize]
+  pub fn get_class<'a>(&self, context: &'a globals::Context) -> &'a EntityClass {
+    &context.data.classes[self.base.class as usize]
   }
 
-  pub fn draw(&self) {
+  pub fn draw(&self, context: &mut globals::Context) {
     if self.base.hidden {
       return;
     }
 
-    self.data.draw(&self.base);
+    self.data.draw(context, &self.base);
   }
 
   pub fn is_physical(&self) -> bool {
@@ -357,20 +436,64 @@ impl Entity {
     }
   }
 
-  pub fn step(&mut self, delta: Time) {
+  // moves without interpolating from the previous position when drawn
+  pub fn teleport(&mut self, pos: Vec3f) {
+    self.set_pos(pos);
+    self.base.update_prev();
+  }
+
+  // moves back to a point between the previous and the current step, 0 = previous
+  pub fn rewind(&mut self, t: FScalar) {
+    let pos = self.base.prev_pos + (self.base.pos - self.base.prev_pos) * t;
+    self.base.angle = util::interpolate_angle(self.base.prev_angle, self.base.angle, t);
+    self.set_pos(pos);
+  }
+
+  pub fn step(&mut self, context: &mut globals::Context, view: &mut screen::GameView, delta: Time) {
     if self.base.hidden {
       return;
     }
 
+    // every step, so whatever moved the entity since is interpolated from here
+    self.base.update_prev();
     self.base.stance_millis += delta;
 
-    self.data.step(&mut self.base, delta);
+    if let Some(trigger) = self.base.route.waiting_for {
+      if view.route_triggers.contains(&trigger) {
+        self.base.route.waiting_for = None;
+        self.base.frozen = false;
+      }
+    }
+
+    self.data.step(context, view, &mut self.base, delta);
+
+    for event in self.base.route.take_events() {
+      if let route::RouteEvent::Trigger(trigger) = event {
+        view.new_route_triggers.push(trigger);
+      }
+
+      self.data.on_route_event(context, view, &mut self.base, event);
+    }
+
+    let animation = animation::get_animation(context, &self.base);
+    if self.base.animation.step(animation, delta) {
+      self.data.on_animation_finished(context, &mut self.base);
+    }
 
     if let Some(ref mut collision) = self.collision {
       collision.update_isometry(&self.base);
     }
   }
 
+  pub fn on_collision(&mut self, push: Vec3f, impact: Vec3f) {
+    self.data.on_collision(&mut self.base, push, impact);
+  }
+
+  // from explosions, and collisions for vehicles. weapons don't hit anything yet
+  pub fn damage(&mut self, context: &globals::Context, amount: IScalar) {
+    self.data.damage(context, &mut self.base, amount);
+  }
+
   pub fn despawn(&mut self) -> bool {
     self.data.despawn_action(&mut self.base)
   }
@@ -2,70 +2,31 @@ use super::*;
 use opensrme_common::*;
 use std::collections::HashMap;
 
+// owned by main() and passed down explicitly, the current Screen is kept separately
 pub struct Context {
   pub running: bool,
   pub archive: Box<Archive>,
   pub platform: Box<Platform>,
+  // None draws straight to the window
+  pub viewport: Option<viewport::Viewport>,
   pub realtime: Time,
   pub time: Time,
   pub delta: Time,
+  // fixed length of a simulation step
+  pub tick: Time,
+  // how fast the game runs compared to real time
+  pub time_scale: FScalar,
+  // not in the original game: the level routes' node bytes are read as events, and people follow the routes
+  pub route_events: bool,
+  // not in the original game, set while recording or playing back a replay.
+  // replays only have the input state, so nothing that reads events itself (dialogs, the console) can be used
+  pub recording_input: bool,
+  // how far between the previous and current simulation step drawing is, 0..1
+  pub alpha: FScalar,
   pub data: DataContext,
-  pub palette_images: Vec<Vec<PlatformId>>,
+  pub textures: image::TextureCache,
   pub font_images: Vec<PlatformId>,
   pub levels: HashMap<LevelId, Level>,
-  pub game: *mut screen::GameScreen,
-  pub screen: Option<Box<screen::Screen>>,
-  pub input: input::InputContext
-}
-
-
-// https://doc.rust-lang.org/std/mem/fn.transmute.html
-struct R<'a, T>(&'a mut T);
-unsafe fn extend_lifetime<'b, T>(r: R<'b, T>) -> R<'static, T> {
-    std::mem::transmute::<R<'b, T>, R<'static, T>>(r)
-}
-
-static mut GLOBAL_CONTEXT: Option<Context> = None;
-
-pub fn get_context() -> &'static mut Context {
-  unsafe {
-    if let Some(ref mut context) = &mut GLOBAL_CONTEXT {
-      context
-    } else {
-      panic!("Context uninitialized");
-    }
-  }
-}
-
-pub fn set_context(context: Context) {
-  unsafe {
-    GLOBAL_CONTEXT = Some(context);
-  }
-}
-
-pub fn get_game() -> &'static mut super::screen::GameScreen {
-  let context = get_context();
-
-  unsafe {
-    if context.game == std::ptr::null_mut() {
-      panic!("GameScreen is null");
-    } else {
-      extend_lifetime(R(&mut (*context.game))).0
-    }
-  }
-}
-
-pub fn set_screen<T: screen::Screen>(screen: T) where T: Sized+'static {
-  let context = get_context();
-
-  context.screen = Some(Box::new(screen));
-  context.game = std::ptr::null_mut();
-}
-
-pub fn set_game(screen: screen::GameScreen) {
-  let context = get_context();
-
-  let mut our_box = Box::new(screen);
-  context.game = &mut (*our_box) as *mut screen::GameScreen;
-  context.screen = Some(our_box);
+  pub input: input::InputContext,
+  pub rng: rng::GameRng
 }
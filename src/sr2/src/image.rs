@@ -1,136 +1,272 @@
 use super::*;
-use byteorder::{BigEndian, ByteOrder};
-use crc::crc32;
-
-pub fn new(path: &str) -> PlatformId {
-  let context = globals::get_context();
+use std::io::Read;
+use std::collections::HashMap;
+
+// the game's images are indexed PNGs, recolored by swapping the palette.
+// they're decoded once, textures are made for each palette that gets used
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedImage {
+  pub size: Vec3i,
+  pub indices: Vec<u8>,
+  // PLTE
+  pub palette: Palette,
+  // tRNS, kept when the palette is replaced
+  pub alphas: Vec<u8>
+}
 
-  context.platform.load_image_from_filename(&(*context.archive), path)
+fn invalid_data(message: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
 }
 
-pub fn plte_chunk(size: u32, palette: &Palette) -> Vec<u8> {
-  let mut result = vec![];
+pub fn decode_indexed<T: Read>(file: T) -> io::Result<IndexedImage> {
+  let mut decoder = png::Decoder::new(file);
+  // indices instead of colors
+  decoder.set_transformations(png::Transformations::IDENTITY);
+
+  let (info, mut reader) = decoder.read_info().map_err(|error| invalid_data(&error.to_string()[..]))?;
+  if info.color_type != png::ColorType::Indexed {
+    return Err(invalid_data("Not an indexed image"));
+  }
+
+  let mut data = vec![0; info.buffer_size()];
+  reader.next_frame(&mut data).map_err(|error| invalid_data(&error.to_string()[..]))?;
+
+  // rows are packed with less than 8 bits per pixel
+  let bits = info.bit_depth as usize;
+  let per_byte = 8 / bits;
+  let mask = ((1u16 << bits) - 1) as u8;
 
-  result.push('P' as u8);
-  result.push('L' as u8);
-  result.push('T' as u8);
-  result.push('E' as u8);
+  let mut indices = Vec::with_capacity((info.width * info.height) as usize);
+  for y in 0..info.height as usize {
+    let row = &data[y * info.line_size..(y + 1) * info.line_size];
 
-  for i in 0..size/3 {
-    let color = palette.colors[i as usize];
-    result.push(color.r);
-    result.push(color.g);
-    result.push(color.b);
+    for x in 0..info.width as usize {
+      let byte = row[x / per_byte];
+      let shift = 8 - bits * (x % per_byte + 1);
+      indices.push((byte >> shift) & mask);
+    }
   }
 
-  result
-}
+  let png_info = reader.info();
 
-pub fn write_chunk(data: Vec<u8>) -> Vec<u8> {
-  let mut result = vec![];
+  let mut palette = Palette {
+    colors: vec![]
+  };
 
-  result.extend(&data[4..]);
-  let crc = crc32::checksum_ieee(&data[..]);
+  if let Some(ref plte) = png_info.palette {
+    for rgb in plte.chunks(3) {
+      if rgb.len() == 3 {
+        palette.colors.push(Color { r: rgb[0], g: rgb[1], b: rgb[2], a: 255 });
+      }
+    }
+  }
 
-  let mut buf = [0; 4];
-  BigEndian::write_u32(&mut buf, crc);
-  result.extend(&buf[..]);
+  let mut alphas = vec![255; palette.colors.len()];
+  if let Some(ref trns) = png_info.trns {
+    for (i, alpha) in trns.iter().enumerate() {
+      if i < alphas.len() {
+        alphas[i] = *alpha;
+      }
+    }
+  }
 
-  result
+  Ok(IndexedImage {
+    size: Vec3i::new2(info.width as IScalar, info.height as IScalar),
+    indices,
+    palette,
+    alphas
+  })
 }
 
-pub fn replace_image_palette<T: DataInputStream>(file: &mut T, palette: &Palette) -> io::Result<Vec<u8>> {
-  let mut result = vec![];
-
-  let header = file.read_amount_as_u8(8)?;
-  result.extend(header);
+impl IndexedImage {
+  // None uses the image's own palette
+  pub fn to_image(&self, palette: Option<&Palette>) -> Image {
+    let mut data = Vec::with_capacity(self.indices.len() * 4);
+
+    for index in self.indices.iter() {
+      let index = *index as usize;
+
+      // palettes shorter than the image's keep the original colors
+      let color = palette
+        .and_then(|palette| palette.colors.get(index))
+        .or(self.palette.colors.get(index))
+        .cloned()
+        .unwrap_or(Color { r: 0, g: 0, b: 0, a: 255 });
+      let alpha = self.alphas.get(index).cloned().unwrap_or(255);
+
+      data.push(color.r);
+      data.push(color.g);
+      data.push(color.b);
+      data.push(alpha);
+    }
 
-  let mut finished = false;
-  while !finished {
-    let size_raw = file.read_amount_as_u8(4)?;
-    let name_raw = file.read_amount_as_u8(4)?;
+    Image {
+      size: self.size,
+      data
+    }
+  }
+}
 
-    result.extend(size_raw.clone());
-    result.extend(name_raw.clone());
+pub fn new(context: &mut globals::Context, path: &str) -> PlatformId {
+  context.platform.load_image_from_filename(&(*context.archive), path)
+}
 
-    if name_raw[0] == 'I' as u8 &&
-      name_raw[1] == 'E' as u8 &&
-      name_raw[2] == 'N' as u8 &&
-      name_raw[3] == 'D' as u8 {
-        finished = true;
-        continue;
-      }
+// for images that aren't part of bin.all's image list, like fonts
+pub fn new_with_path_palette(context: &mut globals::Context, path: &str, palette: &Palette) -> PlatformId {
+  let file = match context.archive.open_file(path) {
+    Ok(file) => file,
+    Err(_) => panic!("Unable to open file: {}", path)
+  };
+
+  match decode_indexed(file) {
+    Ok(indexed) => context.platform.new_image(indexed.to_image(Some(palette))),
+    // nothing to recolor
+    Err(_) => new(context, path)
+  }
+}
 
-    let size = BigEndian::read_u32(&size_raw[..]);
-    let data = file.read_amount_as_u8(size as usize + 4)?;
+// decoded once while it has textures, None if the image isn't indexed
+fn get_indexed_image(context: &mut globals::Context, image: ImageId) -> Option<&IndexedImage> {
+  if !context.textures.indexed_images.contains_key(&image) {
+    let filename = context.data.images[image as usize].clone();
 
-    if name_raw[0] == 'P' as u8 &&
-      name_raw[1] == 'L' as u8 &&
-      name_raw[2] == 'T' as u8 &&
-      name_raw[3] == 'E' as u8 {
-        let chunk = plte_chunk(size, &palette);
-        result.extend(write_chunk(chunk));
+    let indexed = match context.archive.open_file(&filename[..]) {
+      Ok(file) => decode_indexed(file).ok(),
+      Err(_) => panic!("Unable to open file: {}", filename)
+    };
 
-        finished = true;
-        continue;
-      } else {
-        result.extend(data);
-      }
+    context.textures.indexed_images.insert(image, indexed);
   }
 
-  let mut endbytes = vec![];
-  file.read_to_end(&mut endbytes)?;
-  result.extend(endbytes);
+  context.textures.indexed_images.get(&image).and_then(|indexed| indexed.as_ref())
+}
 
-  Ok(result)
+// textures are made when first drawn, the least recently used ones are unloaded above the budget
+const TEXTURE_BUDGET: usize = 32 * 1024 * 1024;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct CachedTexture {
+  platform_id: PlatformId,
+  bytes: usize,
+  last_used: u64
 }
 
-pub fn new_with_path_palette(path: &str, palette: &Palette) -> PlatformId {
-  let context = globals::get_context();
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureCache {
+  textures: HashMap<(ImageId, PaletteId), CachedTexture>,
+  // pairs drawn with another pair's texture, see copy_image_palette
+  aliases: HashMap<(ImageId, PaletteId), (ImageId, PaletteId)>,
+  // the textures are made from these, dropped with the image's last texture
+  indexed_images: HashMap<ImageId, Option<IndexedImage>>,
+  used_bytes: usize,
+  budget: usize,
+  uses: u64
+}
 
-  if let Ok(mut file) = context.archive.open_file(path) {
-    if let Ok(data) = replace_image_palette(&mut file, palette) {
-      return context.platform.load_image(&data[..]);
-    } else {
-      panic!("Unable to replace image palette for file: {}", path);
+impl TextureCache {
+  pub fn new() -> Self {
+    TextureCache {
+      textures: HashMap::new(),
+      aliases: HashMap::new(),
+      indexed_images: HashMap::new(),
+      used_bytes: 0,
+      budget: TEXTURE_BUDGET,
+      uses: 0
     }
-  } else {
-    panic!("Unable to open file: {}", path);
   }
 
-  0
+  fn resolve(&self, key: (ImageId, PaletteId)) -> (ImageId, PaletteId) {
+    self.aliases.get(&key).cloned().unwrap_or(key)
+  }
+
+  fn get(&mut self, key: (ImageId, PaletteId)) -> Option<PlatformId> {
+    self.uses += 1;
+    let uses = self.uses;
+
+    self.textures.get_mut(&key).map(|texture| {
+      texture.last_used = uses;
+      texture.platform_id
+    })
+  }
+
+  fn insert(&mut self, platform: &mut Platform, key: (ImageId, PaletteId), platform_id: PlatformId) {
+    let bytes = platform.get_image_size(platform_id)
+      .map(|size| (size.x * size.y * 4) as usize)
+      .unwrap_or(0);
+
+    self.uses += 1;
+    self.used_bytes += bytes;
+    self.textures.insert(key, CachedTexture {
+      platform_id,
+      bytes,
+      last_used: self.uses
+    });
+
+    self.evict(platform, key);
+  }
+
+  // never unloads the texture that was just added
+  fn evict(&mut self, platform: &mut Platform, keep: (ImageId, PaletteId)) {
+    while self.used_bytes > self.budget {
+      let oldest = self.textures.iter()
+        .filter(|&(key, _)| *key != keep)
+        .min_by_key(|&(_, texture)| texture.last_used)
+        .map(|(key, _)| *key);
+
+      match oldest {
+        Some(key) => {
+          let texture = self.textures.remove(&key).unwrap();
+          self.used_bytes -= texture.bytes;
+          platform.unload_image(texture.platform_id);
+
+          if !self.textures.keys().any(|other| other.0 == key.0) {
+            self.indexed_images.remove(&key.0);
+          }
+        },
+        None => break
+      }
+    }
+  }
 }
 
-pub fn load_image(image: ImageId, palette: PaletteId) -> PlatformId {
-  let context = globals::get_context();
+fn create_texture(context: &mut globals::Context, image: ImageId, palette: PaletteId) -> PlatformId {
+  let new_palette = match palette {
+    0 => None,
+    _ => Some(context.data.palettes[palette as usize].clone())
+  };
 
+  let rgba = get_indexed_image(context, image).map(|indexed| indexed.to_image(new_palette.as_ref()));
+
+  match rgba {
+    Some(rgba) => context.platform.new_image(rgba),
+    None => {
+      let filename = context.data.images[image as usize].clone();
+      new(context, &filename[..])
+    }
+  }
+}
+
+pub fn load_image(context: &mut globals::Context, image: ImageId, palette: PaletteId) -> PlatformId {
   let mut palette = palette;
   if palette == -1 {
     palette = 0;
   }
 
-  let platform_id = context.palette_images[palette as usize][image as usize];
-  if platform_id != 0 {
+  let (image, palette) = context.textures.resolve((image, palette));
+
+  if let Some(platform_id) = context.textures.get((image, palette)) {
     return platform_id;
   }
 
-  let filename = &context.data.images[image as usize][..];
-  let platform_id =
-    match palette {
-      0 => new(filename),
-      _ => new_with_path_palette(filename, &context.data.palettes[palette as usize])
-    };
-
-  context.palette_images[palette as usize][image as usize] = platform_id;
+  let platform_id = create_texture(context, image, palette);
+  if platform_id != 0 {
+    context.textures.insert(&mut *context.platform, (image, palette), platform_id);
+  }
 
   platform_id
 }
 
-pub fn copy_image_palette(image: ImageId, source_palette: PaletteId, dest_palette: PaletteId) {
-  let context = globals::get_context();
-
-  load_image(image, source_palette);
-
-  context.palette_images[dest_palette as usize][image as usize] =
-    context.palette_images[source_palette as usize][image as usize];
+// the image is drawn with the source palette when the destination palette is asked for
+pub fn copy_image_palette(context: &mut globals::Context, image: ImageId, source_palette: PaletteId, dest_palette: PaletteId) {
+  context.textures.aliases.insert((image, dest_palette), (image, source_palette));
 }
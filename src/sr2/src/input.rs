@@ -1,6 +1,6 @@
 use opensrme_common::*;
 use super::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Hash, Debug, Copy, Clone, Eq, PartialEq)]
 pub enum InputKey {
@@ -16,39 +16,121 @@ pub enum InputKey {
   Context = 7, // LSB
   Exit = 8, // RSB
 
-  Vehicle = 11 // Enter/Exit
+  Vehicle = 11, // Enter/Exit
+
+  // not in the original game
+  Rebind = 100,
+  ToggleControls = 101,
+  Handbrake = 102,
+  ToggleDebug = 103,
+  Console = 104
 }
 
-pub fn platform_key_to_inputkey(key: Key) -> InputKey {
-  if key.scancode == 41 {
-    // esc
-    return InputKey::Exit;
+impl InputKey {
+  pub fn all() -> &'static [InputKey] {
+    &[
+      InputKey::Up,
+      InputKey::Down,
+      InputKey::Left,
+      InputKey::Right,
+      InputKey::Attack,
+      InputKey::Context,
+      InputKey::Vehicle,
+      InputKey::Exit,
+      InputKey::Rebind,
+      InputKey::ToggleControls,
+      InputKey::Handbrake,
+      InputKey::ToggleDebug,
+      InputKey::Console
+    ]
   }
 
-  return match key.value as char {
-    'a' => InputKey::Left,
-    'd' => InputKey::Right,
-    'w' => InputKey::Up,
-    's' => InputKey::Down,
-    'e' => InputKey::Context,
-    'f' => InputKey::Attack,
-    'v' => InputKey::Vehicle,
-    _ => InputKey::Unknown
+  pub fn name(&self) -> &'static str {
+    match *self {
+      InputKey::Unknown => "Unknown",
+      InputKey::Down    => "Down",
+      InputKey::Right   => "Right",
+      InputKey::Up      => "Up",
+      InputKey::Left    => "Left",
+      InputKey::Attack  => "Attack",
+      InputKey::Context => "Context",
+      InputKey::Exit    => "Exit",
+      InputKey::Vehicle => "Vehicle",
+      InputKey::Rebind  => "Rebind",
+      InputKey::ToggleControls => "ToggleControls",
+      InputKey::Handbrake => "Handbrake",
+      InputKey::ToggleDebug => "ToggleDebug",
+      InputKey::Console => "Console"
+    }
+  }
+
+  pub fn from_id(id: i16) -> Option<InputKey> {
+    for key in InputKey::all().iter() {
+      if *key as i16 == id {
+        return Some(*key);
+      }
+    }
+
+    None
+  }
+
+  pub fn from_name(name: &str) -> Option<InputKey> {
+    for key in InputKey::all().iter() {
+      if key.name().eq_ignore_ascii_case(name) {
+        return Some(*key);
+      }
+    }
+
+    None
   }
 }
 
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct InputContext {
+  pub bindings: bindings::InputBindings,
   pub keys: HashMap<InputKey, Time>,
   pub key_delta: HashMap<InputKey, bool>,
   pub buttons: HashMap<MouseButton, Time>,
   pub button_delta: HashMap<MouseButton, bool>,
   pub mouse: Vec3i,
   pub mouse_delta: Vec3i,
-  pub mouse_scroll: i32
+  pub mouse_scroll: i32,
+
+  pub controllers: HashSet<ControllerId>,
+  // deadzone is already applied
+  pub left_stick: Vec3f,
+  pub right_stick: Vec3f,
+  // last input came from a controller rather than the keyboard/mouse
+  pub using_controller: bool,
+
+  held_bindings: HashSet<bindings::Binding>,
+  axes: HashMap<ControllerAxis, FScalar>,
+  // game time of the event being processed
+  time: Time
+}
+
+pub const STICK_DEADZONE: FScalar = 0.25;
+
+fn apply_deadzone(stick: Vec3f) -> Vec3f {
+  let len = stick.len2();
+
+  if len < STICK_DEADZONE {
+    Vec3f::default()
+  } else {
+    // rescale so that movement starts at 0 right outside of the deadzone
+    let new_len = util::fmin(1., (len - STICK_DEADZONE) / (1. - STICK_DEADZONE));
+    stick * (new_len / len)
+  }
 }
 
 impl InputContext {
+  pub fn new(bindings: bindings::InputBindings) -> Self {
+    let mut input = InputContext::default();
+    input.bindings = bindings;
+
+    input
+  }
+
   pub fn step(&mut self) {
     self.key_delta.clear();
     self.button_delta.clear();
@@ -56,33 +138,138 @@ impl InputContext {
     self.mouse_scroll = 0;
   }
 
-  pub fn process_platform_event(&mut self, event: Event) {
-    let context = globals::get_context();
+  fn is_inputkey_held(&self, inputkey: InputKey) -> bool {
+    for binding in self.bindings.get(inputkey).iter() {
+      if self.held_bindings.contains(binding) {
+        return true;
+      }
+    }
 
-    match event {
-      Event::Key { key, pressed } => {
-        let inputkey = platform_key_to_inputkey(key);
+    false
+  }
 
-        if inputkey == InputKey::Unknown {
-          return;
-        }
+  fn process_bindings(&mut self, physical: &[bindings::Binding], inputkeys: Vec<InputKey>, pressed: bool) {
+    for binding in physical.iter() {
+      if pressed {
+        self.held_bindings.insert(*binding);
+      } else {
+        self.held_bindings.remove(binding);
+      }
+    }
 
-        if pressed {
-          self.keys.insert(inputkey, context.time);
-        } else {
-          self.keys.remove(&inputkey);
+    for inputkey in inputkeys {
+      if pressed {
+        if !self.keys.contains_key(&inputkey) {
+          self.keys.insert(inputkey, self.time);
+        }
+      } else {
+        // another binding for the same action is still held
+        if self.is_inputkey_held(inputkey) {
+          continue;
         }
 
-        self.key_delta.insert(inputkey, pressed);
+        self.keys.remove(&inputkey);
+      }
+
+      self.key_delta.insert(inputkey, pressed);
+    }
+  }
+
+  // releases every action, e.g. after the bindings have changed
+  pub fn release_all(&mut self) {
+    for key in self.keys.keys() {
+      self.key_delta.insert(*key, false);
+    }
+
+    self.keys.clear();
+    self.held_bindings.clear();
+  }
+
+  fn get_axis(&self, axis: ControllerAxis) -> FScalar {
+    *self.axes.get(&axis).unwrap_or(&0.)
+  }
+
+  fn update_sticks(&mut self) {
+    self.left_stick = apply_deadzone(Vec3f::new2(self.get_axis(ControllerAxis::LeftX),
+                                                 self.get_axis(ControllerAxis::LeftY)));
+    self.right_stick = apply_deadzone(Vec3f::new2(self.get_axis(ControllerAxis::RightX),
+                                                  self.get_axis(ControllerAxis::RightY)));
+  }
+
+  fn process_controller_axis(&mut self, axis: ControllerAxis, value: FScalar) {
+    let old_value = self.get_axis(axis);
+    self.axes.insert(axis, value);
+
+    if axis == ControllerAxis::LeftTrigger || axis == ControllerAxis::RightTrigger {
+      let was_pressed = old_value >= bindings::TRIGGER_THRESHOLD;
+      let pressed = value >= bindings::TRIGGER_THRESHOLD;
+
+      if pressed != was_pressed {
+        let binding = bindings::Binding::ControllerTrigger(axis);
+        let inputkeys = self.bindings.lookup(binding);
+        self.process_bindings(&[binding], inputkeys, pressed);
+      }
+    } else {
+      self.update_sticks();
+    }
+
+    // ignore small movements inside of the deadzone
+    if self.left_stick.len2() > 0. || self.right_stick.len2() > 0. || self.get_axis(axis) >= bindings::TRIGGER_THRESHOLD {
+      self.using_controller = true;
+    }
+  }
+
+  fn reset_controller_state(&mut self) {
+    let mut released = vec![];
+    for binding in self.held_bindings.iter() {
+      match *binding {
+        bindings::Binding::Controller(_) |
+        bindings::Binding::ControllerTrigger(_) => released.push(*binding),
+        _ => {}
+      }
+    }
+
+    for binding in released {
+      let inputkeys = self.bindings.lookup(binding);
+      self.process_bindings(&[binding], inputkeys, false);
+    }
+
+    self.axes.clear();
+    self.update_sticks();
+  }
+
+  pub fn process_platform_event(&mut self, event: Event, time: Time) {
+    self.time = time;
+
+    match event {
+      Event::Key { .. } | Event::MouseButton { .. } | Event::MousePos { .. } => {
+        self.using_controller = false;
+      },
+      Event::ControllerButton { .. } => {
+        self.using_controller = true;
+      },
+      _ => {}
+    }
+
+    match event {
+      Event::Key { key, pressed } => {
+        let inputkeys = self.bindings.lookup_key(key);
+        let key_bindings = [bindings::Binding::Scancode(key.scancode), bindings::Binding::Char(key.value)];
+
+        self.process_bindings(&key_bindings, inputkeys, pressed);
       },
       Event::MouseButton { pressed, button } => {
         if pressed {
-          self.buttons.insert(button, context.time);
+          self.buttons.insert(button, self.time);
         } else {
           self.buttons.remove(&button);
         }
 
         self.button_delta.insert(button, pressed);
+
+        let binding = bindings::Binding::Mouse(button);
+        let inputkeys = self.bindings.lookup(binding);
+        self.process_bindings(&[binding], inputkeys, pressed);
       },
       Event::MousePos { pos, delta } => {
         self.mouse = pos;
@@ -94,6 +281,27 @@ impl InputContext {
           MouseScroll::Down => 1
         };
       },
+      Event::ControllerAdded(id) => {
+        self.controllers.insert(id);
+      },
+      Event::ControllerRemoved(id) => {
+        self.controllers.remove(&id);
+
+        // only a single set of axes is tracked, so this resets every controller
+        self.reset_controller_state();
+
+        if self.controllers.len() == 0 {
+          self.using_controller = false;
+        }
+      },
+      Event::ControllerButton { pressed, button, .. } => {
+        let binding = bindings::Binding::Controller(button);
+        let inputkeys = self.bindings.lookup(binding);
+        self.process_bindings(&[binding], inputkeys, pressed);
+      },
+      Event::ControllerAxis { axis, value, .. } => {
+        self.process_controller_axis(axis, value);
+      },
       _ => {}
     }
   }
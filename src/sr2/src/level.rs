@@ -3,6 +3,7 @@ use types::*;
 use super::*;
 use route::*;
 use std::io;
+use std::collections::HashMap;
 
 fn read_levellayer<T: DataInputStream>(file: &mut T) -> io::Result<LevelLayer> {
   let startx:IScalar = file.read_short()? as IScalar;
@@ -59,12 +60,12 @@ fn read_routepart<T: DataInputStream>(file: &mut T) -> io::Result<RoutePart> {
 
   let pos = Vec3f::new2(x, y);
 
-  let unk1 = file.read_unsigned_byte()?;
+  let event = file.read_unsigned_byte()?;
 
   Ok(RoutePart {
     pos,
     distance: 0.,
-    unk1
+    event
   })
 }
 
@@ -77,7 +78,8 @@ fn read_route<T: DataInputStream>(file: &mut T) -> io::Result<Route> {
   }
 
   Ok(Route {
-    parts
+    parts,
+    has_events: false
   })
 }
 
@@ -153,34 +155,35 @@ pub fn read_level<T: DataInputStream>(file: &mut T) -> io::Result<Level> {
   })
 }
 
-pub fn get_level_from_levelid(levelid: LevelId) -> Level {
-  let context = globals::get_context();
-
+pub fn get_level_from_levelid(context: &mut globals::Context, levelid: LevelId) -> Level {
   if context.levels.contains_key(&levelid) {
     context.levels.get(&levelid).unwrap().clone()
   } else {
-    let level = read_level(&mut context.archive.open_file(&context.data.levels[levelid as usize].path[..]).unwrap()).unwrap();
+    let mut level = read_level(&mut context.archive.open_file(&context.data.levels[levelid as usize].path[..]).unwrap()).unwrap();
+    for route in level.routes.iter_mut() {
+      route.has_events = context.route_events;
+    }
+
     context.levels.insert(levelid, level.clone());
     level
   }
 }
 
-pub fn load_images(levelid: LevelId) {
-  let context = globals::get_context();
-
-  for image in context.data.levels[levelid as usize].images.iter() {
-    image::load_image(image.image, image.palette);
+pub fn load_images(context: &mut globals::Context, levelid: LevelId) {
+  for i in 0..context.data.levels[levelid as usize].images.len() {
+    let image = context.data.levels[levelid as usize].images[i].clone();
+    image::load_image(context, image.image, image.palette);
   }
 }
 
-pub fn load_entities(level: &Level) -> Vec<entity::Entity> {
+pub fn load_entities(context: &mut globals::Context, level: &Level) -> Vec<entity::Entity> {
   let mut entities = vec![];
 
   let mut id = 0;
   for level_entity in level.entities.iter() {
-    let mut entity = entity::Entity::new(id, level_entity.class);
+    let mut entity = entity::Entity::new(context, id, level_entity.class);
     id += 1;
-    entity.set_pos(level_entity.pos.into());
+    entity.teleport(level_entity.pos.into());
 
     if level_entity.route != -1 {
       entity.base.route.routeid = Some(level_entity.route);
@@ -192,116 +195,364 @@ pub fn load_entities(level: &Level) -> Vec<entity::Entity> {
   entities
 }
 
-fn level_drawable_aabb(layer: &LevelLayer) -> (Vec3i, Vec3i) {
-  let context = globals::get_context();
-  let tilesize = layer.tilesize.x;
-  let start = layer.start - (layer.tilesize / 2);
-  let size = context.platform.get_size();
-  let scale = 1. / context.platform.get_scale();
-  let translate = context.platform.get_translation();
-  let clipstart = translate * -1;
+// tiles of the grid that are on the screen, for a grid starting at origin
+fn visible_cells(context: &globals::Context, origin: Vec3i, cellsize: IScalar, gridsize: Vec3i) -> (Vec3i, Vec3i) {
+  let view = render::get_view_aabb(context);
 
-  let startx = std::cmp::max(0, (clipstart.x - start.x) / tilesize);
-  let starty = std::cmp::max(0, (clipstart.y - start.y) / tilesize);
-  let endx = std::cmp::min(layer.size.x, startx + (util::iscale_ceil(scale, size.x) + tilesize - 1) / tilesize + 1);
-  let endy = std::cmp::min(layer.size.y, starty + (util::iscale_ceil(scale, size.y) + tilesize - 1) / tilesize + 1);
+  let startx = std::cmp::max(0, (view[0] - origin.x) / cellsize);
+  let starty = std::cmp::max(0, (view[1] - origin.y) / cellsize);
+  let endx = std::cmp::min(gridsize.x, startx + (view[2] - view[0] + cellsize - 1) / cellsize + 1);
+  let endy = std::cmp::min(gridsize.y, starty + (view[3] - view[1] + cellsize - 1) / cellsize + 1);
 
   (Vec3i::new2(startx, starty), Vec3i::new2(endx, endy))
 }
 
-pub fn draw_level_layer(layer: &LevelLayer) {
-  if layer.tilesize.x == 0 || layer.tilesize.y == 0 {
-    return;
+fn get_shadow_sprite(level: &Level, x: IScalar, y: IScalar) -> Option<SpriteId> {
+  if y < 1 || y >= level.tiledata_size.y || x < 0 || x >= level.tiledata_size.x - 1 {
+    return None;
   }
 
-  let (start, end) = level_drawable_aabb(layer);
-  for x in start.x..end.x {
-    for y in start.y..end.y {
-      let tile = layer.tiles[((y * layer.size.x) + x) as usize];
-      if tile >= 0 {
-        sprite::draw_sprite(tile, Vec3i::new2(layer.start.x + x * layer.tilesize.x, layer.start.y + y * layer.tilesize.y), 0);
+  let id = (y * level.tiledata_size.x + x) as usize;
+  if level.tiledata[id] == 4 {
+    return None;
+  }
+
+  if level.tiledata[id - level.tiledata_size.x as usize] == 4 { // wall above
+    if level.tiledata[(id - level.tiledata_size.x as usize) + 1] == 4 { // wall above right
+      if level.tiledata[id + 1] == 4 { // wall right
+        // -+
+        // x|
+        Some(1361)
+      } else {
+        // --
+        // x
+        Some(1359)
       }
+    } else { // bottom right corner
+      // -
+      // x
+      Some(1360)
+    }
+  } else if level.tiledata[id + 1] == 4 { // wall right
+    if level.tiledata[(id - level.tiledata_size.x as usize) + 1] == 4 { // wall above right
+      //  |
+      // x|
+      Some(1357)
+    } else {
+      //
+      // x|
+      Some(1356)
     }
+  } else if level.tiledata[(id - level.tiledata_size.x as usize) + 1] == 4 { // wall above right
+    //  +
+    // x
+    Some(1358)
+  } else {
+    None
   }
 }
 
-pub fn draw_shadows(level: &Level) {
-  let mut id: usize = 0;
-  let ts_half = level.layer1.tilesize / 2;
-  for y in 1..level.tiledata_size.y {
-    id = (y * level.tiledata_size.x) as usize;
-    let draw_y = (y * level.layer1.tilesize.y + ts_half.y) as IScalar;
-    for x in 0..(level.tiledata_size.x - 1) {
-      let pos = Vec3i::new2(x * level.layer1.tilesize.x + ts_half.x, draw_y);
-      if level.tiledata[id] != 4 {
-        if level.tiledata[id - level.tiledata_size.x as usize] == 4 { // wall above
-          if level.tiledata[(id - level.tiledata_size.x as usize) + 1] == 4 { // wall above right
-            if level.tiledata[id + 1] == 4 { // wall right
-              // -+
-              // x|
-              sprite::draw_sprite(1361, pos, 0);
-            } else {
-              // --
-              // x
-              sprite::draw_sprite(1359, pos, 0);
-            }
-          } else { // bottom right corner
-            // -
-            // x
-            sprite::draw_sprite(1360, pos, 0);
-          }
-        } else if level.tiledata[id + 1] == 4 { // wall right
-          if level.tiledata[(id - level.tiledata_size.x as usize) + 1] == 4 { // wall above right
-            //  |
-            // x|
-            sprite::draw_sprite(1357, pos, 0);
-          } else {
-            //
-            // x|
-            sprite::draw_sprite(1356, pos, 0);
-          }
-        } else if level.tiledata[(id - level.tiledata_size.x as usize) + 1] == 4 { // wall above right
-          //  +
-          // x
-          sprite::draw_sprite(1358, pos, 0);
+// not in the original game.
+// static parts of the level are drawn into textures a chunk of tiles at a time
+const CHUNK_TILES: IScalar = 8;
+// chunks that weren't drawn for a while are unloaded above this
+const MAX_CHUNKS: usize = 64;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ChunkSource<'a> {
+  Layer(&'a LevelLayer),
+  Shadows(&'a Level)
+}
+
+impl<'a> ChunkSource<'a> {
+  fn get_gridsize(&self) -> Vec3i {
+    match *self {
+      ChunkSource::Layer(layer) => layer.size,
+      ChunkSource::Shadows(level) => level.tiledata_size
+    }
+  }
+
+  fn get_tilesize(&self) -> Vec3i {
+    match *self {
+      ChunkSource::Layer(layer) => layer.tilesize,
+      ChunkSource::Shadows(level) => level.layer1.tilesize
+    }
+  }
+
+  // where the grid starts, the sprites are drawn in the middle of each tile
+  fn get_origin(&self) -> Vec3i {
+    match *self {
+      ChunkSource::Layer(layer) => layer.start - (layer.tilesize / 2),
+      ChunkSource::Shadows(_) => Vec3i::default()
+    }
+  }
+
+  fn get_sprite(&self, x: IScalar, y: IScalar) -> Option<(SpriteId, Vec3i)> {
+    match *self {
+      ChunkSource::Layer(layer) => {
+        let tile = layer.tiles[((y * layer.size.x) + x) as usize];
+        if tile >= 0 {
+          Some((tile, Vec3i::new2(layer.start.x + x * layer.tilesize.x, layer.start.y + y * layer.tilesize.y)))
+        } else {
+          None
         }
+      },
+      ChunkSource::Shadows(level) => {
+        let half = level.layer1.tilesize / 2;
+        get_shadow_sprite(level, x, y).map(|sprite| {
+          (sprite, Vec3i::new2(x * level.layer1.tilesize.x + half.x, y * level.layer1.tilesize.y + half.y))
+        })
       }
-
-      id += 1;
     }
   }
 }
 
-pub fn draw_objects(level: &Level, entities: &Vec<entity::Entity>, entity_ids: &Vec<EntityId>) {
-  let mut entity_sort_id = 0;
-  let mut entity_sort_order = entities[entity_ids[entity_sort_id] as usize].base.sort_order;
+// frame conditions only come from SetFrame, in the sprite or its children
+fn sprite_is_animated(context: &globals::Context, sprite: SpriteId) -> bool {
+  context.data.sprites[sprite as usize].compiled[0].iter().any(|op| !op.conditions.is_empty())
+}
 
-  for i in 0..level.objects.len() {
-    let object = &level.objects[i];
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct ChunkTile {
+  x: IScalar,
+  y: IScalar,
+  sprite: SpriteId,
+  pos: Vec3i,
+  aabb: [IScalar; 4],
+  animated: bool
+}
 
-    if object.sprite == -1 {
-      continue;
+impl ChunkTile {
+  // the original game draws the tiles column by column
+  fn comes_after(&self, other: &ChunkTile) -> bool {
+    (self.x, self.y) > (other.x, other.y)
+  }
+
+  fn overlaps(&self, other: &ChunkTile) -> bool {
+    render::aabb_overlaps(self.aabb, other.aabb)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Chunk {
+  texture: Option<PlatformId>,
+  pos: Vec3i,
+  size: Vec3i,
+  // drawn over the texture, or everything if the texture couldn't be made
+  sprites: Vec<(SpriteId, Vec3i)>,
+  last_used: u64
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ChunkCache {
+  chunks: HashMap<(IScalar, IScalar), Chunk>,
+  frame: u64
+}
+
+impl ChunkCache {
+  pub fn new() -> Self {
+    ChunkCache::default()
+  }
+
+  // has to be called when what the source draws changes
+  pub fn clear(&mut self, context: &mut globals::Context) {
+    for (_, chunk) in self.chunks.drain() {
+      if let Some(texture) = chunk.texture {
+        context.platform.unload_image(texture);
+      }
+    }
+  }
+
+  // in the order the original game draws them
+  fn get_tiles(context: &globals::Context, source: ChunkSource, chunkpos: Vec3i) -> Vec<ChunkTile> {
+    let gridsize = source.get_gridsize();
+    let start = chunkpos * CHUNK_TILES;
+    let end = Vec3i::new2(std::cmp::min(gridsize.x, start.x + CHUNK_TILES),
+                          std::cmp::min(gridsize.y, start.y + CHUNK_TILES));
+
+    let mut tiles = vec![];
+    for x in start.x..end.x {
+      for y in start.y..end.y {
+        if let Some((sprite, pos)) = source.get_sprite(x, y) {
+          let aabb = sprite::calc_aabb(&context.data.sprites[sprite as usize], pos, 0);
+
+          tiles.push(ChunkTile {
+            x,
+            y,
+            sprite,
+            pos,
+            aabb: [aabb[0] as IScalar, aabb[1] as IScalar, aabb[2] as IScalar, aabb[3] as IScalar],
+            animated: sprite_is_animated(context, sprite)
+          });
+        }
+      }
+    }
+
+    tiles
+  }
+
+  // the tiles that have to be drawn again over the texture to keep the original order where they overlap:
+  // the animated ones and whatever comes after them, and the tiles of the chunk above (drawn before this one)
+  // that come after tiles of this chunk, along with whatever comes after those.
+  // drawing a tile over itself again only differs for half transparent pixels
+  fn get_overlay(tiles: &[ChunkTile], above: &[ChunkTile]) -> Vec<ChunkTile> {
+    let raised: Vec<ChunkTile> = above.iter()
+      .filter(|other| tiles.iter().any(|tile| other.comes_after(tile) && other.overlaps(tile)))
+      .cloned()
+      .collect();
+
+    let mut overlay = vec![];
+    for (i, tile) in tiles.iter().enumerate() {
+      let covered = tile.animated ||
+        tiles[..i].iter().any(|other| other.animated && other.overlaps(tile)) ||
+        raised.iter().any(|other| tile.comes_after(other) && other.overlaps(tile));
+
+      if covered {
+        overlay.push(*tile);
+      }
+    }
+
+    overlay.extend(raised);
+    overlay.sort_by_key(|tile| (tile.x, tile.y));
+    overlay
+  }
+
+  fn bake(context: &mut globals::Context, source: ChunkSource, chunkpos: Vec3i) -> Chunk {
+    let tiles = ChunkCache::get_tiles(context, source, chunkpos);
+    let above = if chunkpos.y > 0 {
+      ChunkCache::get_tiles(context, source, chunkpos - Vec3i::new2(0, 1))
+    } else {
+      vec![]
+    };
+
+    let mut min = Vec3i::new2(IScalar::max_value(), IScalar::max_value());
+    let mut max = Vec3i::new2(IScalar::min_value(), IScalar::min_value());
+
+    for tile in tiles.iter().filter(|tile| !tile.animated) {
+      min.x = std::cmp::min(min.x, tile.aabb[0]);
+      min.y = std::cmp::min(min.y, tile.aabb[1]);
+      max.x = std::cmp::max(max.x, tile.aabb[2]);
+      max.y = std::cmp::max(max.y, tile.aabb[3]);
+    }
+
+    let overlay = ChunkCache::get_overlay(&tiles, &above);
+    let mut chunk = Chunk {
+      texture: None,
+      pos: min,
+      size: max - min,
+      sprites: overlay.iter().map(|tile| (tile.sprite, tile.pos)).collect(),
+      last_used: 0
+    };
+
+    if !tiles.iter().any(|tile| !tile.animated) || chunk.size.x <= 0 || chunk.size.y <= 0 {
+      return chunk;
+    }
+
+    let texture = match context.platform.new_render_target(chunk.size) {
+      Some(texture) => texture,
+      None => {
+        // everything one by one, along with what's raised from the chunk above
+        let raised = overlay.iter().filter(|tile| tile.y < chunkpos.y * CHUNK_TILES);
+        let mut sprites: Vec<ChunkTile> = tiles.iter().chain(raised).cloned().collect();
+        sprites.sort_by_key(|tile| (tile.x, tile.y));
+        chunk.sprites = sprites.iter().map(|tile| (tile.sprite, tile.pos)).collect();
+        return chunk;
+      }
+    };
+
+    let translation = context.platform.get_translation();
+    let scale = context.platform.get_scale();
+    let target = context.platform.get_render_target();
+
+    context.platform.set_render_target(Some(texture));
+    context.platform.reset();
+    context.platform.set_color(Color { r: 0, g: 0, b: 0, a: 0 });
+    context.platform.clear();
+    context.platform.translate(min * -1);
+
+    for tile in tiles.iter().filter(|tile| !tile.animated) {
+      sprite::draw_sprite(context, tile.sprite, tile.pos, 0);
+    }
+
+    context.platform.set_render_target(target);
+    context.platform.reset();
+    context.platform.translate(translation);
+    context.platform.scale(scale);
+
+    chunk.texture = Some(texture);
+    chunk
+  }
+
+  pub fn draw(&mut self, context: &mut globals::Context, source: ChunkSource) {
+    let tilesize = source.get_tilesize();
+    let gridsize = source.get_gridsize();
+    if tilesize.x == 0 || tilesize.y == 0 {
+      return;
     }
 
-    if entity_sort_id < entities.len() {
-      while entity_sort_order < object.pos.y {
-        let entity = &entities[entity_ids[entity_sort_id] as usize];
-        entity.draw();
+    self.frame += 1;
+
+    let (start, end) = visible_cells(context, source.get_origin(), tilesize.x, gridsize);
+    if start.x >= end.x || start.y >= end.y {
+      return;
+    }
+
+    // one more around the edges, for sprites bigger than their tile
+    let chunks = Vec3i::new2((gridsize.x + CHUNK_TILES - 1) / CHUNK_TILES, (gridsize.y + CHUNK_TILES - 1) / CHUNK_TILES);
+    let first = Vec3i::new2(std::cmp::max(0, start.x / CHUNK_TILES - 1), std::cmp::max(0, start.y / CHUNK_TILES - 1));
+    let last = Vec3i::new2(std::cmp::min(chunks.x - 1, (end.x - 1) / CHUNK_TILES + 1),
+                           std::cmp::min(chunks.y - 1, (end.y - 1) / CHUNK_TILES + 1));
+
+    for x in first.x..last.x + 1 {
+      for y in first.y..last.y + 1 {
+        if !self.chunks.contains_key(&(x, y)) {
+          let chunk = ChunkCache::bake(context, source, Vec3i::new2(x, y));
+          self.chunks.insert((x, y), chunk);
+        }
+
+        let chunk = self.chunks.get_mut(&(x, y)).unwrap();
+        chunk.last_used = self.frame;
 
-        entity_sort_id += 1;
-        if entity_sort_id >= entities.len() {
-          break;
+        if let Some(texture) = chunk.texture {
+          context.platform.draw_region(texture, 0, 0, chunk.size.x, chunk.size.y, 0, None, chunk.pos.x, chunk.pos.y);
         }
 
-        entity_sort_order = entity.base.sort_order;
+        for &(sprite, pos) in chunk.sprites.iter() {
+          sprite::draw_sprite(context, sprite, pos, 0);
+        }
       }
     }
 
-    sprite::draw_sprite(object.sprite, object.pos, 0);
+    self.evict(context);
   }
 
-  for i in entity_sort_id..entity_ids.len() {
-    entities[entity_ids[i] as usize].draw();
+  fn evict(&mut self, context: &mut globals::Context) {
+    while self.chunks.len() > MAX_CHUNKS {
+      let oldest = self.chunks.iter()
+        .filter(|&(_, chunk)| chunk.last_used != self.frame)
+        .min_by_key(|&(_, chunk)| chunk.last_used)
+        .map(|(key, _)| *key);
+
+      match oldest {
+        Some(key) => {
+          if let Some(texture) = self.chunks.remove(&key).unwrap().texture {
+            context.platform.unload_image(texture);
+          }
+        },
+        None => break
+      }
+    }
+  }
+}
+
+// objects are added before entities, so they're drawn first at the same y
+pub fn queue_objects(context: &globals::Context, queue: &mut render::RenderQueue, level: &Level) {
+  for object in level.objects.iter() {
+    if object.sprite == -1 {
+      continue;
+    }
+
+    queue.push_sprite(context, render::Layer::World, object.pos.y, object.sprite, object.pos, 0);
   }
 }
 
@@ -325,14 +576,59 @@ pub fn get_tiledata_for_pos(level: &Level, pos: Vec3f) -> i8 {
   get_tiledata_for_tilepos(level, pos_to_tilepos(pos))
 }
 
+pub fn tiledata_is_sidewalk(tiledata: LevelTileData) -> bool {
+  tiledata == 9 || tiledata == 36
+}
+
 pub fn pos_is_sidewalk(level: &Level, pos: Vec3f) -> bool {
-  let tiledata = get_tiledata_for_pos(level, pos);
+  tiledata_is_sidewalk(get_tiledata_for_pos(level, pos))
+}
 
-  tiledata == 9 || tiledata == 36
+pub fn tiledata_is_impassable(tiledata: LevelTileData) -> bool {
+  tiledata == 1 || tiledata == 2 || tiledata == 3 || tiledata == 4
 }
 
 pub fn tilepos_is_impassable(level: &Level, tilepos: Vec3i) -> bool {
-  let tiledata = get_tiledata_for_tilepos(level, tilepos);
+  tiledata_is_impassable(get_tiledata_for_tilepos(level, tilepos))
+}
 
-  tiledata == 1 || tiledata == 2 || tiledata == 3 || tiledata == 4
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn tile(x: IScalar, y: IScalar, size: IScalar, animated: bool) -> ChunkTile {
+    let pos = Vec3i::new2(x * util::TILESIZE, y * util::TILESIZE);
+
+    ChunkTile {
+      x,
+      y,
+      sprite: 0,
+      pos,
+      aabb: [pos.x, pos.y, pos.x + size, pos.y + size],
+      animated
+    }
+  }
+
+  fn get_positions(overlay: &[ChunkTile]) -> Vec<(IScalar, IScalar)> {
+    overlay.iter().map(|tile| (tile.x, tile.y)).collect()
+  }
+
+  #[test]
+  fn overlay_keeps_tiles_over_animated_ones() {
+    let big = util::TILESIZE * 2;
+    let tiles = [tile(0, 0, big, false), tile(0, 1, big, true), tile(0, 2, big, false), tile(3, 0, util::TILESIZE, false)];
+
+    assert_eq!(get_positions(&ChunkCache::get_overlay(&tiles, &[])), vec![(0, 1), (0, 2)]);
+  }
+
+  #[test]
+  fn overlay_raises_later_tiles_from_the_chunk_above() {
+    let big = util::TILESIZE * 2;
+    let y = CHUNK_TILES;
+    // the one above and to the right comes later in the original order, the one straight above doesn't
+    let above = [tile(0, y - 1, big, false), tile(1, y - 1, big, false)];
+    let tiles = [tile(0, y, big, false), tile(1, y, util::TILESIZE, false)];
+
+    assert_eq!(get_positions(&ChunkCache::get_overlay(&tiles, &above)), vec![(1, y - 1), (1, y)]);
+  }
 }
@@ -1,8 +1,7 @@
 extern crate opensrme_common;
 extern crate encoding;
-extern crate rand;
 extern crate byteorder;
-extern crate crc;
+extern crate png;
 extern crate nalgebra;
 extern crate ncollide2d;
 
@@ -18,11 +17,22 @@ mod vehicle;
 mod person;
 mod collision;
 mod entity;
+mod animation;
 mod controller;
 mod route;
 mod util;
 mod input;
+mod bindings;
+mod replay;
+mod options;
+mod rng;
+mod particle;
+mod pathfinding;
 mod image;
+mod render;
+mod debug;
+mod console;
+mod viewport;
 mod text;
 mod dialog;
 mod screen;
@@ -33,6 +43,9 @@ use opensrme_common::*;
 
 use std::io;
 
+// longest amount of real time simulated after a single frame
+const MAX_FRAME_DELTA: Time = 250;
+
 pub fn check(archive: &Archive) -> io::Result<bool> {
   let files = archive.list_dir(".")?;
   for file in files {
@@ -57,25 +70,40 @@ fn draw_splash(archive: &Box<Archive>, platform: &mut Platform) {
   platform.swap();
 }
 
-pub fn main(archive: Box<Archive>, _args: Vec<String>) {
-  let mut platform = SDL2Platform::new("Saints Row 2", 800, 800);
+pub fn main(archive: Box<Archive>, args: Vec<String>) {
+  let options = options::parse_args(&args[..]);
 
-  draw_splash(&archive, &mut platform);
+  let mut replay = None;
+  if let Some(ref path) = options.replay {
+    match replay::InputReplay::new(&path[..]) {
+      Ok(new_replay) => replay = Some(new_replay),
+      Err(error) => {
+        println!("Unable to load replay {}: {}", path, error);
+        return;
+      }
+    }
+  }
 
-  let datacontext = read_bin_all(&(*archive)).unwrap();
-  println!("{:?}", datacontext.levels);
+  let rng = if let Some(ref replay) = replay {
+    rng::GameRng::new(replay.seed)
+  } else if let Some(seed) = options.seed {
+    rng::GameRng::new(seed)
+  } else {
+    rng::GameRng::new_random_seed()
+  };
+  println!("Random seed: {}", rng.seed);
 
-  //let mut images = vec![];
-  let mut palette_images = vec![];
+  let window_size = match options.scale_mode {
+    Some(_) => viewport::VIRTUAL_SIZE * 2,
+    None => Vec3i::new2(800, 800)
+  };
 
-  for _i in 0..datacontext.palettes.len() {
-    let mut images = vec![];
-    for _j in 0..datacontext.images.len() {
-      images.push(0 as PlatformId);
-    }
+  let mut platform = SDL2Platform::new_with_vsync("Saints Row 2", window_size.x as i16, window_size.y as i16, options.vsync);
 
-    palette_images.push(images);
-  }
+  draw_splash(&archive, &mut platform);
+
+  let datacontext = read_bin_all(&(*archive)).unwrap();
+  println!("{:?}", datacontext.levels);
 
   let mut font_images = vec![];
   for _i in 0..datacontext.fonts.len() {
@@ -102,25 +130,27 @@ pub fn main(archive: Box<Archive>, _args: Vec<String>) {
     }
   }
 
-  let context = globals::Context {
+  let mut context = globals::Context {
     running: true,
     archive: archive,
+    viewport: options.scale_mode.map(|mode| viewport::Viewport::new(mode, platform.get_size())),
     platform: Box::new(platform),
     realtime: instant_get_millis(),
     time: 1,
     delta: 0,
+    tick: options.get_tick(),
+    time_scale: 1.,
+    recording_input: false,
+    route_events: options.route_events,
+    alpha: 1.,
     data: datacontext,
-    palette_images,
+    textures: image::TextureCache::new(),
     font_images,
     levels: std::collections::HashMap::new(),
-    game: std::ptr::null_mut(),
-    screen: None,
-    input: input::InputContext::default()
+    input: input::InputContext::new(bindings::InputBindings::load_or_default()),
+    rng
   };
 
-  globals::set_context(context);
-  let mut context = globals::get_context();
-
   /*for i in context.data.images.iter() {
     println!("{}", i);
     context.images.push(PaletteImage {
@@ -131,7 +161,7 @@ pub fn main(archive: Box<Archive>, _args: Vec<String>) {
 
   for i in 0..context.data.fonts.len() {
     println!("Loading font #{}", i);
-    text::load_font(i as FontId);
+    text::load_font(&mut context, i as FontId);
   }
 
   /*for i in 0..context.data.images.len() {
@@ -140,39 +170,92 @@ pub fn main(archive: Box<Archive>, _args: Vec<String>) {
     }
 
     println!("Loading image #{}", i);
-    image::load_image(i as ImageId, 0);
+    image::load_image(&mut context, i as ImageId, 0);
   }*/
 
-  let mut game = screen::GameScreen::new(0);
-  game.init();
+  let levelid = match replay {
+    Some(ref replay) => replay.levelid,
+    None => 0
+  };
+
+  let mut recorder = None;
+  if let Some(ref path) = options.record {
+    match replay::InputRecorder::new(&path[..], levelid, context.rng.seed) {
+      Ok(new_recorder) => recorder = Some(new_recorder),
+      Err(error) => println!("Unable to record to {}: {}", path, error)
+    }
+  }
+
+  context.recording_input = recorder.is_some() || replay.is_some();
+
+  let mut game = screen::GameScreen::new(&mut context, levelid);
+  game.init(&mut context);
 
-  //context.game = Some(game.clone());
-  //context.screen = Some(game.clone());
-  globals::set_game(game);
+  if let Some(count) = options.benchmark_collision {
+    game.benchmark_collision(&mut context, count);
+    return;
+  }
+
+  let mut screen: Box<Screen> = Box::new(game);
 
   //let image = context.platform.load_image_from_filename(archive, "Car_Police.png");
 
   let mut last_second = context.realtime;
   let mut fps = 0;
+  let mut accumulator: Time = 0;
+  let mut scaled_time: FScalar = 0.;
   while context.running {
     let lasttime = context.realtime;
     context.realtime = instant_get_millis();
-    context.delta = (context.realtime - lasttime) * 1;
-    context.time += context.delta;
 
-    context.input.step();
+    // avoid trying to catch up forever after a long stall, or with a fast time scale
+    let frame_delta = std::cmp::min(context.realtime - lasttime, MAX_FRAME_DELTA);
+    // the parts of a millisecond are kept for the next frame, for slow time scales
+    scaled_time = util::fmin(scaled_time + frame_delta as FScalar * context.time_scale, MAX_FRAME_DELTA as FScalar);
+    accumulator += scaled_time as Time;
+    scaled_time = scaled_time.fract();
 
     let mut newsize: Option<Vec3i> = None;
-    while let Some(event) = context.platform.poll_event() {
-      if let Event::Quit = event {
-        context.running = false;
-        break;
+    let mut targets_reset = false;
+
+    if replay.is_some() {
+      // the recorded input is used instead, only check for window events
+      while let Some(event) = context.platform.poll_event() {
+        let event = match context.viewport {
+          Some(ref mut viewport) => viewport.handle_event(event),
+          None => event
+        };
+
+        match event {
+          Event::Quit => context.running = false,
+          Event::Resize(new_size) => newsize = Some(new_size),
+          Event::RenderTargetsReset => targets_reset = true,
+          _ => {}
+        }
       }
-
-      context.input.process_platform_event(event);
-
-      if let Event::Resize(new_size) = event {
-        newsize = Some(new_size);
+    } else {
+      while let Some(event) = context.platform.poll_event() {
+        let event = match context.viewport {
+          Some(ref mut viewport) => viewport.handle_event(event),
+          None => event
+        };
+
+        if let Event::Quit = event {
+          context.running = false;
+          break;
+        }
+
+        let consumed = screen.input(&mut context, event);
+
+        if !consumed {
+          context.input.process_platform_event(event, context.time);
+        }
+
+        match event {
+          Event::Resize(new_size) => newsize = Some(new_size),
+          Event::RenderTargetsReset => targets_reset = true,
+          _ => {}
+        }
       }
     }
 
@@ -180,18 +263,68 @@ pub fn main(archive: Box<Archive>, _args: Vec<String>) {
       break;
     }
 
-    if let Some(ref mut screen) = context.screen {
-      if let Some(newsize) = newsize {
-        screen.set_size(newsize);
+    if let Some(newsize) = newsize {
+      screen.set_size(&mut context, newsize);
+    }
+
+    if targets_reset {
+      if let Some(ref mut viewport) = context.viewport {
+        viewport.reset_target(&mut *context.platform);
       }
 
-      screen.step(context.delta);
+      screen.render_targets_reset(&mut context);
+    }
 
-      if !context.running {
-        break;
+    while accumulator >= context.tick && context.running {
+      accumulator -= context.tick;
+      context.delta = context.tick;
+
+      if let Some(ref mut replay) = replay {
+        match replay.read_frame(&mut context.input) {
+          Some(delta) => context.delta = delta,
+          None => {
+            println!("Replay finished");
+            context.running = false;
+            break;
+          }
+        }
       }
 
-      screen.draw();
+      context.time += context.delta;
+
+      let mut recording_failed = false;
+      if let Some(ref mut recorder) = recorder {
+        if let Err(error) = recorder.record_frame(context.delta, &context.input) {
+          println!("Unable to record input, stopping: {}", error);
+          recording_failed = true;
+        }
+      }
+
+      if recording_failed {
+        recorder = None;
+      }
+
+      let delta = context.delta;
+      screen.step(&mut context, delta);
+
+      // presses and releases are only seen by a single tick
+      context.input.step();
+    }
+
+    if !context.running {
+      break;
+    }
+
+    context.alpha = accumulator as FScalar / context.tick as FScalar;
+
+    if let Some(ref mut viewport) = context.viewport {
+      viewport.begin(&mut *context.platform);
+    }
+
+    screen.draw(&mut context);
+
+    if let Some(ref mut viewport) = context.viewport {
+      viewport.end(&mut *context.platform);
     }
 
     context.platform.swap();
@@ -205,11 +338,20 @@ pub fn main(archive: Box<Archive>, _args: Vec<String>) {
       fps += 1;
     }
 
-    let mut sleep = 16;
-    let millis = instant_get_millis() - context.realtime;
-    if millis < sleep {
-      sleep -= millis;
-      std::thread::sleep(std::time::Duration::from_millis(sleep));
+    // swap() already waits for the display with vsync
+    if !context.platform.has_vsync() {
+      let mut sleep = context.tick;
+      let millis = instant_get_millis() - context.realtime;
+      if millis < sleep {
+        sleep -= millis;
+        std::thread::sleep(std::time::Duration::from_millis(sleep));
+      }
+    }
+  }
+
+  if let Some(ref mut recorder) = recorder {
+    if let Err(error) = recorder.finish() {
+      println!("Unable to finish recording: {}", error);
     }
   }
 
@@ -0,0 +1,139 @@
+use opensrme_common::*;
+use super::viewport::ScaleMode;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameOptions {
+  pub record: Option<String>,
+  pub replay: Option<String>,
+  pub seed: Option<u64>,
+  pub tickrate: u64,
+  pub vsync: bool,
+  // draw at the phone's resolution and scale it to the window
+  pub scale_mode: Option<ScaleMode>,
+  pub route_events: bool,
+  pub benchmark_collision: Option<usize>
+}
+
+pub const DEFAULT_TICKRATE: u64 = 60;
+const MAX_TICKRATE: u64 = 1000;
+
+impl Default for GameOptions {
+  fn default() -> Self {
+    GameOptions {
+      record: None,
+      replay: None,
+      seed: None,
+      tickrate: DEFAULT_TICKRATE,
+      vsync: true,
+      scale_mode: None,
+      route_events: false,
+      benchmark_collision: None
+    }
+  }
+}
+
+impl GameOptions {
+  // length of a single simulation tick in milliseconds
+  pub fn get_tick(&self) -> Time {
+    1000 / self.tickrate
+  }
+}
+
+fn usage() {
+  println!("Options:");
+  println!("  --record <file>   record input to a replay file");
+  println!("  --replay <file>   play back a replay file instead of reading input");
+  println!("  --seed <number>   seed for the random number generator");
+  println!("  --tickrate <hz>   simulation updates per second (default {})", DEFAULT_TICKRATE);
+  println!("  --no-vsync        don't wait for the display's refresh when drawing");
+  println!("  --virtual-resolution");
+  println!("                    draw at 240x320 and scale it up to fit the window");
+  println!("  --integer-scale   like --virtual-resolution, only scaled by whole numbers");
+  println!("  --route-events    people follow the level's routes, with the node bytes read as events");
+  println!("  --benchmark-collision <entities>");
+  println!("                    add entities to the level, time the collision checks and exit");
+}
+
+pub fn parse_args(args: &[String]) -> GameOptions {
+  let mut options = GameOptions::default();
+
+  let mut i = 0;
+  while i < args.len() {
+    let arg = &args[i][..];
+    let value = args.get(i + 1).cloned();
+
+    match arg {
+      "--record" | "--replay" => {
+        if value.is_none() {
+          println!("Missing value for {}", arg);
+          usage();
+          break;
+        }
+
+        if arg == "--record" {
+          options.record = value;
+        } else {
+          options.replay = value;
+        }
+
+        i += 2;
+      },
+      "--seed" => {
+        match value.and_then(|value| value.parse::<u64>().ok()) {
+          Some(seed) => options.seed = Some(seed),
+          None => {
+            println!("Invalid value for --seed");
+            usage();
+          }
+        }
+
+        i += 2;
+      },
+      "--tickrate" => {
+        match value.and_then(|value| value.parse::<u64>().ok()) {
+          Some(tickrate) if tickrate > 0 && tickrate <= MAX_TICKRATE => options.tickrate = tickrate,
+          _ => {
+            println!("Invalid value for --tickrate");
+            usage();
+          }
+        }
+
+        i += 2;
+      },
+      "--benchmark-collision" => {
+        match value.and_then(|value| value.parse::<usize>().ok()) {
+          Some(count) => options.benchmark_collision = Some(count),
+          None => {
+            println!("Invalid value for --benchmark-collision");
+            usage();
+          }
+        }
+
+        i += 2;
+      },
+      "--virtual-resolution" => {
+        options.scale_mode = Some(ScaleMode::Aspect);
+        i += 1;
+      },
+      "--integer-scale" => {
+        options.scale_mode = Some(ScaleMode::Integer);
+        i += 1;
+      },
+      "--route-events" => {
+        options.route_events = true;
+        i += 1;
+      },
+      "--no-vsync" => {
+        options.vsync = false;
+        i += 1;
+      },
+      _ => {
+        println!("Unknown option: {}", arg);
+        usage();
+        i += 1;
+      }
+    }
+  }
+
+  options
+}
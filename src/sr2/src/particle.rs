@@ -0,0 +1,118 @@
+use super::*;
+
+// not in the original game, which uses effect clips
+const MAX_PARTICLES: usize = 1024;
+const EXPLOSION_PARTICLES: usize = 24;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ParticleType {
+  Smoke,
+  Fire,
+  Explosion
+}
+
+impl ParticleType {
+  fn get_lifetime(&self) -> Time {
+    match *self {
+      ParticleType::Smoke     => 1500,
+      ParticleType::Fire      => 400,
+      ParticleType::Explosion => 700
+    }
+  }
+
+  // t = 0..1 over the lifetime
+  fn get_size(&self, t: FScalar) -> IScalar {
+    match *self {
+      ParticleType::Smoke     => 3 + (t * 6.) as IScalar,
+      ParticleType::Fire      => 4 - (t * 3.) as IScalar,
+      ParticleType::Explosion => 6 + (t * 8.) as IScalar
+    }
+  }
+
+  fn get_color(&self, t: FScalar) -> Color {
+    let fade = ((1. - t) * 255.) as u8;
+
+    match *self {
+      ParticleType::Smoke     => Color { r: 80, g: 80, b: 80, a: fade / 2 },
+      ParticleType::Fire      => Color { r: 255, g: 160 - (t * 120.) as u8, b: 0, a: fade },
+      ParticleType::Explosion => Color { r: 255, g: 200 - (t * 180.) as u8, b: 40, a: fade }
+    }
+  }
+
+  // units per second
+  fn get_speed(&self) -> FScalar {
+    match *self {
+      ParticleType::Smoke     => 8.,
+      ParticleType::Fire      => 4.,
+      ParticleType::Explosion => 60.
+    }
+  }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Particle {
+  particle_type: ParticleType,
+  pos: Vec3f,
+  velocity: Vec3f,
+  age: Time
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Particles {
+  particles: Vec<Particle>
+}
+
+impl Particles {
+  pub fn new() -> Self {
+    Particles::default()
+  }
+
+  pub fn emit(&mut self, context: &mut globals::Context, particle_type: ParticleType, pos: Vec3f) {
+    if self.particles.len() >= MAX_PARTICLES {
+      return;
+    }
+
+    let angle = util::pick_cosmetic_float(context, util::TWO_PI);
+    let speed = particle_type.get_speed() * (0.5 + util::pick_cosmetic_float(context, 1.));
+    let mut velocity = util::cossin(angle) * speed;
+
+    // smoke rises, which is up on the screen
+    if particle_type == ParticleType::Smoke {
+      velocity.y -= 10.;
+    }
+
+    self.particles.push(Particle {
+      particle_type,
+      pos,
+      velocity,
+      age: 0
+    });
+  }
+
+  pub fn explode(&mut self, context: &mut globals::Context, pos: Vec3f) {
+    for _i in 0..EXPLOSION_PARTICLES {
+      self.emit(context, ParticleType::Explosion, pos);
+    }
+  }
+
+  pub fn step(&mut self, delta: Time) {
+    let seconds = delta as FScalar / 1000.;
+
+    for particle in self.particles.iter_mut() {
+      particle.age += delta;
+      particle.pos = particle.pos + particle.velocity * seconds;
+    }
+
+    self.particles.retain(|particle| particle.age < particle.particle_type.get_lifetime());
+  }
+
+  pub fn draw(&self, context: &mut globals::Context) {
+    for particle in self.particles.iter() {
+      let t = particle.age as FScalar / particle.particle_type.get_lifetime() as FScalar;
+      let size = particle.particle_type.get_size(t);
+
+      context.platform.set_color(particle.particle_type.get_color(t));
+      context.platform.fill_rect(particle.pos.x as IScalar - size / 2, particle.pos.y as IScalar - size / 2, size, size);
+    }
+  }
+}
@@ -0,0 +1,429 @@
+use super::*;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::rc::Rc;
+
+// not in the original game, which only has the level's authored routes
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Mover {
+  // prefers sidewalks, crosses roads at intersections
+  Pedestrian,
+  // stays on roads, in the direction of the traffic
+  Vehicle
+}
+
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+// gives up after looking at this many tiles, so unreachable goals don't search the whole level
+const MAX_SEARCH_NODES: usize = 20000;
+const MAX_CACHED_PATHS: usize = 256;
+// a goal that moved at most this many tiles only gets the path extended
+const REPLAN_EXTEND_DISTANCE: IScalar = 3;
+// in tiles
+const NEAREST_PASSABLE_RADIUS: IScalar = 4;
+
+const NEIGHBOURS: [(IScalar, IScalar); 8] = [
+  (1, 0), (-1, 0), (0, 1), (0, -1),
+  (1, 1), (1, -1), (-1, 1), (-1, -1)
+];
+
+fn get_pedestrian_cost(level: &Level, tilepos: Vec3i) -> Option<u32> {
+  if level::tilepos_is_impassable(level, tilepos) {
+    return None;
+  }
+
+  let tiledata = level::get_tiledata_for_tilepos(level, tilepos);
+  if level::tiledata_is_sidewalk(tiledata) {
+    Some(STRAIGHT_COST)
+  } else if vehicle::is_intersection(tiledata) {
+    Some(STRAIGHT_COST * 2)
+  } else if vehicle::is_road(tiledata) {
+    Some(STRAIGHT_COST * 6)
+  } else {
+    Some(STRAIGHT_COST + STRAIGHT_COST / 2)
+  }
+}
+
+fn get_vehicle_cost(level: &Level, from: Vec3i, to: Vec3i) -> Option<u32> {
+  let to_tiledata = level::get_tiledata_for_tilepos(level, to);
+  if !vehicle::is_road(to_tiledata) {
+    return None;
+  }
+
+  let from_tiledata = level::get_tiledata_for_tilepos(level, from);
+
+  // anything goes on intersections, or to get back onto the road
+  if !vehicle::is_road(from_tiledata) || vehicle::is_intersection(from_tiledata) {
+    return Some(STRAIGHT_COST + STRAIGHT_COST / 2);
+  }
+
+  let angle = util::vec_angle((to - from).into());
+  if vehicle::road_allows_direction(from_tiledata, angle) {
+    Some(STRAIGHT_COST)
+  } else {
+    None
+  }
+}
+
+fn is_passable(level: &Level, mover: Mover, tilepos: Vec3i) -> bool {
+  match mover {
+    Mover::Pedestrian => !level::tilepos_is_impassable(level, tilepos),
+    Mover::Vehicle => vehicle::is_road(level::get_tiledata_for_tilepos(level, tilepos))
+  }
+}
+
+// goals are often where the mover can't go, like an alarm on the sidewalk for a vehicle
+fn find_nearest_passable(level: &Level, mover: Mover, tilepos: Vec3i) -> Option<Vec3i> {
+  if is_passable(level, mover, tilepos) {
+    return Some(tilepos);
+  }
+
+  for radius in 1..NEAREST_PASSABLE_RADIUS + 1 {
+    for y in -radius..radius + 1 {
+      for x in -radius..radius + 1 {
+        // only the ring, the inside has been checked already
+        if x.abs() != radius && y.abs() != radius {
+          continue;
+        }
+
+        let other = Vec3i::new2(tilepos.x + x, tilepos.y + y);
+        if is_passable(level, mover, other) {
+          return Some(other);
+        }
+      }
+    }
+  }
+
+  None
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct SearchNode {
+  estimate: u32,
+  cost: u32,
+  tilepos: (IScalar, IScalar)
+}
+
+// BinaryHeap is a max-heap, the lowest estimate has to come first
+impl Ord for SearchNode {
+  fn cmp(&self, other: &Self) -> Ordering {
+    other.estimate.cmp(&self.estimate)
+      .then_with(|| self.cost.cmp(&other.cost))
+  }
+}
+
+impl PartialOrd for SearchNode {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+fn get_heuristic(from: (IScalar, IScalar), to: (IScalar, IScalar)) -> u32 {
+  let dx = (from.0 - to.0).abs() as u32;
+  let dy = (from.1 - to.1).abs() as u32;
+
+  let diagonal = std::cmp::min(dx, dy);
+  let straight = std::cmp::max(dx, dy) - diagonal;
+
+  diagonal * DIAGONAL_COST + straight * STRAIGHT_COST
+}
+
+// A* from start to goal, both included in the result
+pub fn find_path(level: &Level, mover: Mover, start: Vec3i, goal: Vec3i) -> Option<Vec<Vec3i>> {
+  let start = (start.x, start.y);
+  let goal = (goal.x, goal.y);
+
+  let mut open = BinaryHeap::new();
+  let mut costs: HashMap<(IScalar, IScalar), u32> = HashMap::new();
+  let mut came_from: HashMap<(IScalar, IScalar), (IScalar, IScalar)> = HashMap::new();
+
+  costs.insert(start, 0);
+  open.push(SearchNode {
+    estimate: get_heuristic(start, goal),
+    cost: 0,
+    tilepos: start
+  });
+
+  let mut searched = 0;
+
+  while let Some(node) = open.pop() {
+    if node.tilepos == goal {
+      let mut path = vec![Vec3i::new2(goal.0, goal.1)];
+      let mut current = goal;
+      while let Some(previous) = came_from.get(&current) {
+        path.push(Vec3i::new2(previous.0, previous.1));
+        current = *previous;
+      }
+
+      path.reverse();
+      return Some(path);
+    }
+
+    // already found a cheaper way here
+    if costs.get(&node.tilepos).map_or(false, |cost| *cost < node.cost) {
+      continue;
+    }
+
+    searched += 1;
+    if searched > MAX_SEARCH_NODES {
+      break;
+    }
+
+    let from = Vec3i::new2(node.tilepos.0, node.tilepos.1);
+
+    for (i, &(x, y)) in NEIGHBOURS.iter().enumerate() {
+      let diagonal = i >= 4;
+      // vehicles follow the road tiles, which only go straight
+      if diagonal && mover == Mover::Vehicle {
+        continue;
+      }
+
+      let to = Vec3i::new2(from.x + x, from.y + y);
+
+      // no cutting corners past walls
+      if diagonal && (level::tilepos_is_impassable(level, Vec3i::new2(from.x + x, from.y)) ||
+                      level::tilepos_is_impassable(level, Vec3i::new2(from.x, from.y + y))) {
+        continue;
+      }
+
+      let step_cost = match mover {
+        Mover::Pedestrian => get_pedestrian_cost(level, to),
+        Mover::Vehicle => get_vehicle_cost(level, from, to)
+      };
+
+      let step_cost = match step_cost {
+        Some(step_cost) if diagonal => step_cost * DIAGONAL_COST / STRAIGHT_COST,
+        Some(step_cost) => step_cost,
+        None => continue
+      };
+
+      let cost = node.cost + step_cost;
+      let to = (to.x, to.y);
+      if costs.get(&to).map_or(true, |old_cost| cost < *old_cost) {
+        costs.insert(to, cost);
+        came_from.insert(to, node.tilepos);
+        open.push(SearchNode {
+          estimate: cost + get_heuristic(to, goal),
+          cost,
+          tilepos: to
+        });
+      }
+    }
+  }
+
+  None
+}
+
+// the level doesn't change, so paths can be kept until the cache is full
+pub struct Pathfinder {
+  cache: HashMap<(Mover, (IScalar, IScalar), (IScalar, IScalar)), Option<Rc<Vec<Vec3i>>>>
+}
+
+impl Pathfinder {
+  pub fn new() -> Self {
+    Pathfinder {
+      cache: HashMap::new()
+    }
+  }
+
+  pub fn get_path(&mut self, level: &Level, mover: Mover, start: Vec3i, goal: Vec3i) -> Option<Rc<Vec<Vec3i>>> {
+    let key = (mover, (start.x, start.y), (goal.x, goal.y));
+    if let Some(path) = self.cache.get(&key) {
+      return path.clone();
+    }
+
+    if self.cache.len() >= MAX_CACHED_PATHS {
+      self.cache.clear();
+    }
+
+    let path = find_path(level, mover, start, goal).map(Rc::new);
+    self.cache.insert(key, path.clone());
+    path
+  }
+}
+
+fn get_tile_middle(tilepos: Vec3i) -> Vec3f {
+  let half = (util::TILESIZE / 2) as FScalar;
+  level::tilepos_to_pos(tilepos) + Vec3f::new2(half, half)
+}
+
+// an entity walking or driving somewhere, followed as a route
+#[derive(Debug, Clone, PartialEq)]
+pub struct Navigation {
+  pub mover: Mover,
+  pub start: Vec3f,
+  pub goal: Vec3f,
+  path: Vec<Vec3i>
+}
+
+impl Navigation {
+  pub fn new(pathfinder: &mut Pathfinder, level: &Level, mover: Mover, start: Vec3f, goal: Vec3f) -> Option<Self> {
+    let goal_tilepos = level::pos_to_tilepos(goal);
+    let reachable_tilepos = find_nearest_passable(level, mover, goal_tilepos)?;
+    let goal = if reachable_tilepos == goal_tilepos {
+      goal
+    } else {
+      get_tile_middle(reachable_tilepos)
+    };
+
+    let path = pathfinder.get_path(level, mover, level::pos_to_tilepos(start), reachable_tilepos)?;
+
+    Some(Navigation {
+      mover,
+      start,
+      goal,
+      path: (*path).clone()
+    })
+  }
+
+  // returns false if the new goal can't be reached, the old path is kept then.
+  // if the start changed, the route has to be followed from the beginning again
+  pub fn set_goal(&mut self, pathfinder: &mut Pathfinder, level: &Level, pos: Vec3f, goal: Vec3f) -> bool {
+    let old_goal = level::pos_to_tilepos(self.goal);
+    let new_goal = level::pos_to_tilepos(goal);
+
+    if old_goal == new_goal {
+      self.goal = goal;
+      return true;
+    }
+
+    if !is_passable(level, self.mover, new_goal) {
+      return false;
+    }
+
+    // close to the old goal, only the end has to be planned
+    let moved = (new_goal - old_goal).abs().max2();
+    if moved <= REPLAN_EXTEND_DISTANCE {
+      if let Some(extension) = pathfinder.get_path(level, self.mover, old_goal, new_goal) {
+        self.path.extend(extension.iter().skip(1));
+        self.goal = goal;
+        return true;
+      }
+    }
+
+    match Navigation::new(pathfinder, level, self.mover, pos, goal) {
+      Some(navigation) => {
+        *self = navigation;
+        true
+      },
+      None => false
+    }
+  }
+
+  // through the middle of each tile, starting and ending at the exact positions
+  pub fn get_route(&self) -> route::Route {
+    let mut route = route::Route::default();
+
+    route.parts.push(route::RoutePart {
+      pos: self.start,
+      ..route::RoutePart::default()
+    });
+
+    if self.path.len() > 2 {
+      for tilepos in self.path[1..self.path.len() - 1].iter() {
+        route.parts.push(route::RoutePart {
+          pos: get_tile_middle(*tilepos),
+          ..route::RoutePart::default()
+        });
+      }
+    }
+
+    route.parts.push(route::RoutePart {
+      pos: self.goal,
+      ..route::RoutePart::default()
+    });
+
+    route.set_distances();
+    route
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // # is a wall, . a sidewalk, anything else open ground
+  fn make_level(rows: &[&str]) -> Level {
+    let size = Vec3i::new2(rows[0].len() as IScalar, rows.len() as IScalar);
+    let tiledata = rows.iter().flat_map(|row| row.chars()).map(|tile| match tile {
+      '#' => 1,
+      '.' => 9,
+      _ => 0
+    }).collect();
+
+    let layer = LevelLayer {
+      start: Vec3i::default(),
+      tilesize: Vec3i::new2(util::TILESIZE, util::TILESIZE),
+      size,
+      tiles: vec![]
+    };
+
+    Level {
+      layer1: layer.clone(),
+      layer2: layer,
+      objects: vec![],
+      tilesize: Vec3f::new2(util::TILESIZE as FScalar, util::TILESIZE as FScalar),
+      tiledata_size: size,
+      tiledata,
+      tile_gangdata: vec![],
+      entities: vec![],
+      routes: vec![]
+    }
+  }
+
+  fn assert_walkable(level: &Level, path: &[Vec3i]) {
+    for pair in path.windows(2) {
+      let step = (pair[1] - pair[0]).abs();
+      assert!(step.x <= 1 && step.y <= 1 && step != Vec3i::default(), "{:?} isn't a single step", pair);
+    }
+
+    for tilepos in path.iter() {
+      assert!(!level::tilepos_is_impassable(level, *tilepos), "{:?} is a wall", tilepos);
+    }
+  }
+
+  #[test]
+  fn straight_path() {
+    let level = make_level(&[
+      "#######",
+      "#.....#",
+      "#######"
+    ]);
+
+    let path = find_path(&level, Mover::Pedestrian, Vec3i::new2(1, 1), Vec3i::new2(5, 1)).unwrap();
+    assert_eq!(path, (1..6).map(|x| Vec3i::new2(x, 1)).collect::<Vec<_>>());
+  }
+
+  #[test]
+  fn detour_around_walls() {
+    let level = make_level(&[
+      "#######",
+      "#..#..#",
+      "#..#..#",
+      "#.....#",
+      "#######"
+    ]);
+
+    let start = Vec3i::new2(1, 1);
+    let goal = Vec3i::new2(5, 1);
+    let path = find_path(&level, Mover::Pedestrian, start, goal).unwrap();
+
+    assert_eq!(path.first(), Some(&start));
+    assert_eq!(path.last(), Some(&goal));
+    assert_walkable(&level, &path);
+    assert!(path.iter().any(|tilepos| tilepos.y == 3), "{:?} doesn't go around the wall", path);
+  }
+
+  #[test]
+  fn unreachable_goal() {
+    let level = make_level(&[
+      "#######",
+      "#..#..#",
+      "#..#..#",
+      "#######"
+    ]);
+
+    assert_eq!(find_path(&level, Mover::Pedestrian, Vec3i::new2(1, 1), Vec3i::new2(5, 1)), None);
+  }
+}
@@ -3,10 +3,49 @@ use entity::*;
 
 pub const PLAYER_SPEED: FScalar = 60.;
 
+// not in the original game
+// how far pedestrians notice alarms
+const PERCEPTION_RADIUS: FScalar = 96.;
+const FLEE_SPEED: FScalar = 50.;
+const FLEE_TIME: Time = 4000;
+// vehicles moving faster than this knock pedestrians down
+const KNOCKDOWN_SPEED: FScalar = 30.;
+// damage per unit of speed of the vehicle
+const KNOCKDOWN_DAMAGE: FScalar = 0.1;
+// 1 in CROSS_ROAD_CHANCE pedestrians reaching an intersection cross it
+const CROSS_ROAD_CHANCE: IScalar = 4;
+// police and gangsters run to alarms instead of away from them
+const RESPONSE_SPEED: FScalar = 50.;
+// gangsters give up chasing the player this far away
+const CHASE_RADIUS: FScalar = 192.;
+const CHASE_REPLAN_TIME: Time = 500;
+
+// something pedestrians run away from, seen by everyone on the next step
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Alarm {
+  pub pos: Vec3f
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Crossing {
+  No,
+  // still on the sidewalk, walking towards the road
+  ToRoad,
+  OnRoad
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct SidewalkData {
   pub walking_direction: Vec3f,
-  pub walking_angle: Angle
+  pub walking_angle: Angle,
+
+  crossing: Crossing,
+  flee_angle: Angle,
+  flee_millis: Time,
+
+  navigation: Option<pathfinding::Navigation>,
+  chasing: bool,
+  replan_millis: Time
 }
 
 pub struct PlayerData {
@@ -42,8 +81,6 @@ fn step_base_person(entity: &mut EntityBase, delta: Time) -> bool {
     return true;
   }
 
-  entity.update_prev();
-
   match entity.stance {
     EntityStance::Punching => {
       // TODO
@@ -74,32 +111,265 @@ impl SidewalkData {
   pub fn new() -> Self {
     SidewalkData {
       walking_direction: Vec3f::default(),
-      walking_angle: 0.
+      walking_angle: 0.,
+
+      crossing: Crossing::No,
+      flee_angle: 0.,
+      flee_millis: 0,
+
+      navigation: None,
+      chasing: false,
+      replan_millis: 0
     }
   }
 
-  fn pick_sidewalk_direction(&mut self, entity: &mut EntityBase) {
-    let angle: Angle = util::pick_int(4) as f64 * util::HALF_PI;
-    let class = entity.get_class().clone();
+  fn pick_sidewalk_direction(&mut self, context: &mut globals::Context, entity: &mut EntityBase) {
+    let angle: Angle = util::pick_int(context, 4) as f64 * util::HALF_PI;
+    let class = entity.get_class(context).clone();
     self.walking_direction.x = class.width * angle.cos();
     self.walking_direction.y = class.height * angle.sin();
     self.walking_angle = angle;
   }
 
-  fn step(&mut self, entity: &mut EntityBase, delta: Time) -> bool {
+  fn start_fleeing(&mut self, entity: &mut EntityBase, from: Vec3f) {
+    // snapped to the four directions, like walking
+    let angle = util::vec_angle(entity.pos - from);
+    self.flee_angle = util::normalize_angle((angle / util::HALF_PI).round() * util::HALF_PI);
+    self.flee_millis = FLEE_TIME;
+    self.crossing = Crossing::No;
+    self.stop_navigating(entity);
+
+    entity.speed = FLEE_SPEED;
+    entity.set_new_stance(EntityStance::Running);
+  }
+
+  fn responds_to_alarms(entity: &EntityBase) -> bool {
+    entity.entity_type == EntityType::Police || entity.entity_type == EntityType::Gangster
+  }
+
+  fn check_alarms(&mut self, view: &mut screen::GameView, entity: &mut EntityBase) {
+    let responds = SidewalkData::responds_to_alarms(entity);
+    if responds && self.navigation.is_some() {
+      return;
+    }
+
+    for alarm in view.alarms.iter() {
+      if (alarm.pos - entity.pos).len2() < PERCEPTION_RADIUS {
+        if responds {
+          self.start_navigating(view.pathfinder, view.level, entity, alarm.pos);
+        } else {
+          self.start_fleeing(entity, alarm.pos);
+        }
+
+        return;
+      }
+    }
+  }
+
+  fn start_navigating(&mut self, pathfinder: &mut pathfinding::Pathfinder, level: &Level, entity: &mut EntityBase, goal: Vec3f) -> bool {
+    let navigation = pathfinding::Navigation::new(pathfinder, level, pathfinding::Mover::Pedestrian, entity.pos, goal);
+    if let Some(navigation) = navigation {
+      entity.route.set_route(navigation.get_route(), 0.);
+      entity.following_route = true;
+      entity.speed = RESPONSE_SPEED;
+      entity.set_new_stance(EntityStance::Running);
+
+      self.navigation = Some(navigation);
+      self.crossing = Crossing::No;
+      true
+    } else {
+      false
+    }
+  }
+
+  fn stop_navigating(&mut self, entity: &mut EntityBase) {
+    if self.navigation.take().is_some() {
+      entity.route.reset_route(false);
+      entity.following_route = false;
+    }
+
+    self.chasing = false;
+  }
+
+  fn find_player_pos(view: &screen::GameView) -> Option<Vec3f> {
+    view.other_entities()
+      .find(|other| other.base.entity_type == EntityType::Player && !other.base.hidden)
+      .map(|other| other.base.pos)
+  }
+
+  // gangsters go after the player when close, following them as they move
+  fn step_chase(&mut self, context: &globals::Context, view: &mut screen::GameView, entity: &mut EntityBase, delta: Time) {
+    let player_pos = match SidewalkData::find_player_pos(view) {
+      Some(pos) if (pos - entity.pos).len2() < CHASE_RADIUS => pos,
+      _ => {
+        if self.chasing {
+          self.stop_navigating(entity);
+          entity.set_new_stance(EntityStance::Standing);
+        }
+
+        return;
+      }
+    };
+
+    if !self.chasing {
+      if self.navigation.is_none() && (player_pos - entity.pos).len2() < PERCEPTION_RADIUS {
+        self.chasing = self.start_navigating(view.pathfinder, view.level, entity, player_pos);
+        self.replan_millis = 0;
+      }
+
+      return;
+    }
+
+    // close enough, TODO: attack
+    if (player_pos - entity.pos).len2() < entity.get_class(context).width * 2. {
+      self.stop_navigating(entity);
+      entity.set_new_stance(EntityStance::Standing);
+      return;
+    }
+
+    self.replan_millis += delta;
+    if self.replan_millis < CHASE_REPLAN_TIME {
+      return;
+    }
+    self.replan_millis = 0;
+
+    if let Some(ref mut navigation) = self.navigation {
+      let old_start = navigation.start;
+      if navigation.set_goal(view.pathfinder, view.level, entity.pos, player_pos) {
+        let progress = entity.route.progress;
+        entity.route.set_route(navigation.get_route(), 0.);
+        if navigation.start == old_start {
+          entity.route.progress = progress;
+        }
+      }
+    }
+  }
+
+  // returns true if a vehicle ran into the pedestrian
+  fn check_knockdown(&mut self, context: &globals::Context, view: &mut screen::GameView, entity: &mut EntityBase, delta: Time) -> bool {
+    if delta == 0 {
+      return false;
+    }
+
+    let width = entity.get_class(context).width;
+
+    for other in view.other_entities() {
+      if other.base.hidden || !other.base.entity_type.is_vehicle() {
+        continue;
+      }
+
+      let speed = (other.base.pos - other.base.prev_pos).len2() * 1000. / delta as FScalar;
+      if speed < KNOCKDOWN_SPEED {
+        continue;
+      }
+
+      if (other.base.pos - entity.pos).len2() < other.get_class(context).width + width {
+        // runs away from the vehicle after getting up
+        self.start_fleeing(entity, other.base.pos);
+
+        entity.speed = 0.;
+        entity.health -= (speed * KNOCKDOWN_DAMAGE).ceil() as IScalar;
+        if entity.health <= 0 {
+          entity.set_new_stance(EntityStance::Dead);
+        } else {
+          entity.set_new_stance(EntityStance::LyingDown);
+        }
+
+        view.new_alarms.push(Alarm {
+          pos: entity.pos
+        });
+        return true;
+      }
+    }
+
+    false
+  }
+
+  fn step_flee(&mut self, context: &globals::Context, view: &screen::GameView, entity: &mut EntityBase, delta: Time) {
+    if self.flee_millis <= delta {
+      self.flee_millis = 0;
+      entity.set_new_stance(EntityStance::Standing);
+      return;
+    }
+
+    self.flee_millis -= delta;
+
+    entity.angle = self.flee_angle;
+    entity.speed = FLEE_SPEED;
+    entity.move_forward(delta);
+
+    let ahead = entity.pos + util::cossin(self.flee_angle) * entity.get_class(context).width;
+    if level::tilepos_is_impassable(view.level, level::pos_to_tilepos(ahead)) {
+      entity.pos = entity.prev_pos;
+      entity.update_pos();
+      // try going around it next time
+      self.flee_angle = util::normalize_angle(self.flee_angle + util::HALF_PI);
+    }
+  }
+
+  // at the edge of the sidewalk, maybe cross over to the other side
+  fn try_crossing_road(&mut self, context: &mut globals::Context, view: &screen::GameView, entity: &EntityBase) -> bool {
+    let ahead = level::get_tiledata_for_pos(view.level, entity.pos + self.walking_direction);
+    if !vehicle::is_intersection(ahead) ||
+      !vehicle::can_cross_road(view.vehicle_state.get_trafficlight(), self.walking_angle) {
+        return false;
+      }
+
+    if util::pick_int(context, CROSS_ROAD_CHANCE) != 0 {
+      return false;
+    }
+
+    self.crossing = Crossing::ToRoad;
+    true
+  }
+
+  fn step(&mut self, context: &mut globals::Context, view: &mut screen::GameView, entity: &mut EntityBase, delta: Time) -> bool {
+    if entity.stance != EntityStance::Dead && entity.stance != EntityStance::LyingDown {
+      if self.check_knockdown(context, view, entity, delta) {
+        return true;
+      }
+
+      if self.flee_millis == 0 {
+        self.check_alarms(view, entity);
+      }
+    }
+
     if step_base_person(entity, delta) {
       return true;
     }
 
+    if self.flee_millis > 0 {
+      entity.set_new_stance(EntityStance::Running);
+      self.step_flee(context, view, entity, delta);
+      return false;
+    }
+
+    if entity.entity_type == EntityType::Gangster {
+      self.step_chase(context, view, entity, delta);
+    }
+
+    // step_base_person already moved along the route
+    if self.navigation.is_some() {
+      if entity.route.route.is_none() {
+        self.stop_navigating(entity);
+        entity.set_new_stance(EntityStance::Standing);
+      }
+
+      return false;
+    }
+
+    // scripted by the level's route, until it ends
+    if entity.following_route || entity.frozen {
+      return false;
+    }
+
     match entity.stance {
       EntityStance::Standing => {
-        let level = &globals::get_game().level;
-
         for _i in 0..4 {
-          self.pick_sidewalk_direction(entity);
-          if level::pos_is_sidewalk(level, entity.pos + self.walking_direction) {
+          self.pick_sidewalk_direction(context, entity);
+          if level::pos_is_sidewalk(view.level, entity.pos + self.walking_direction) {
             entity.set_new_stance(EntityStance::Walking);
-            entity.speed = 15. + util::pick_float(15.);
+            entity.speed = 15. + util::pick_float(context, 15.);
             break;
           }
         }
@@ -111,11 +381,34 @@ impl SidewalkData {
 
         entity.move_forward(delta);
 
-        if !level::pos_is_sidewalk(&globals::get_game().level, entity.pos + self.walking_direction) {
+        let ahead = entity.pos + self.walking_direction;
+        let blocked = match self.crossing {
+          Crossing::No => {
+            !level::pos_is_sidewalk(view.level, ahead) && !self.try_crossing_road(context, view, entity)
+          },
+          Crossing::ToRoad => {
+            if !level::pos_is_sidewalk(view.level, entity.pos) {
+              self.crossing = Crossing::OnRoad;
+            }
+
+            false
+          },
+          Crossing::OnRoad => {
+            // made it to the other side
+            if level::pos_is_sidewalk(view.level, entity.pos) {
+              self.crossing = Crossing::No;
+            }
+
+            level::tilepos_is_impassable(view.level, level::pos_to_tilepos(ahead))
+          }
+        };
+
+        if blocked {
           entity.pos.x = entity.prev_pos.x;
           entity.pos.y = entity.prev_pos.y;
           entity.angle = old_angle;
           entity.set_new_stance(EntityStance::Standing);
+          self.crossing = Crossing::No;
         }
       },
       _ => {}
@@ -130,23 +423,34 @@ impl PlayerData {
     PlayerData {}
   }
 
-  fn step(&mut self, entity: &mut EntityBase, delta: Time) {
-    // TODO
-    // if entity.flags & 0x4000 != 0 { return; }
+  fn step(&mut self, context: &mut globals::Context, view: &mut screen::GameView, entity: &mut EntityBase, delta: Time) {
+    if entity.frozen {
+      return;
+    }
+
     if step_base_person(entity, delta) {
       return;
     }
 
     // TODO: shooting, sliding
 
-    let game = globals::get_game();
-    game.playercontroller.process(delta, self);
+    if context.input.key_delta.get(&input::InputKey::Attack) == Some(&true) {
+      view.new_alarms.push(Alarm {
+        pos: entity.pos
+      });
+    }
+
+    // taken out of the view while processing, as the controller looks at the view too
+    if let Some(playercontroller) = view.playercontroller.take() {
+      playercontroller.process(context, view, entity, delta, self);
+      view.playercontroller = Some(playercontroller);
+    }
 
     // TODO: check for nearby car (or possibly other objects?)
   }
 }
 
-fn get_palette_id(entity_type: EntityType) -> PaletteId {
+fn get_palette_id(context: &mut globals::Context, entity_type: EntityType) -> PaletteId {
   if entity_type == EntityType::Player {
     11
   } else if entity_type == EntityType::Police {
@@ -155,7 +459,7 @@ fn get_palette_id(entity_type: EntityType) -> PaletteId {
              entity_type == EntityType::Pedestrian ||
              entity_type == EntityType::VehiclePedestrian ||
              entity_type == EntityType::Type7) {
-    EntityBase::pick_npc_person_palette()
+    EntityBase::pick_npc_person_palette(context)
   } else if entity_type == EntityType::Gangster {
     // TODO
     0
@@ -165,41 +469,20 @@ fn get_palette_id(entity_type: EntityType) -> PaletteId {
   }
 }
 
-pub fn draw(entity: &EntityBase) {
-  if entity.stance == EntityStance::Riding {
-    return;
-  }
-
-  let context = globals::get_context();
-  let class = entity.get_class();
-
-  /*if class.clip == -1 {
-      return;
-    }*/
-
-  let mut stance = entity.stance;
-  if stance == EntityStance::Unknown {
-    stance = EntityStance::Running;
-  }
-
-  //println!("{:?} {:?}", class, stance);
+pub fn draw(context: &mut globals::Context, entity: &EntityBase) {
+  let current_sprite = match entity.animation.get_sprite(context, entity.get_draw_angle(context)) {
+    Some(sprite) => sprite,
+    None => return
+  };
 
-  let index = class.clip + stance as i32;
-  if index == -1 {
-    return;
-  }
-
-  let clip = &context.data.clips[index as usize];
-  let clip_angle = &clip[util::get_angle_in_clip(entity.angle, clip.len())];
-  let current_sprite = clip_angle[util::get_frame_in_clip(entity.stance_millis, 700, clip_angle.len())];
-
-  let imageid = sprite::get_image_from_sprite(current_sprite).unwrap();
-  sprite::draw_sprite_palette(current_sprite, entity.pos.into(), 0, &vec![(imageid, entity.palette)]);
+  let imageid = sprite::get_image_from_sprite(context, current_sprite).unwrap();
+  let pos = entity.get_draw_pos(context);
+  sprite::draw_sprite_palette(context, current_sprite, pos.into(), 0, &vec![(imageid, entity.palette)]);
 }
 
 impl EntityData for PersonData {
-  fn init(&mut self, entity: &mut EntityBase) {
-    entity.palette = get_palette_id(entity.entity_type);
+  fn init(&mut self, context: &mut globals::Context, entity: &mut EntityBase) {
+    entity.palette = get_palette_id(context, entity.entity_type);
 
     match entity.entity_type {
       EntityType::Player => {
@@ -210,9 +493,9 @@ impl EntityData for PersonData {
     }
   }
 
-  fn get_collision_info(&self, entity: &EntityBase) -> Option<collision::ShapeInfo> {
+  fn get_collision_info(&self, context: &globals::Context, entity: &EntityBase) -> Option<collision::ShapeInfo> {
     if entity.entity_type == EntityType::Player {
-      let class = &entity.get_class();
+      let class = entity.get_class(context);
 
       Some(collision::ShapeInfo {
         shape: collision::Shape::Circle(class.width as i32),
@@ -225,22 +508,50 @@ impl EntityData for PersonData {
     }
   }
 
-  fn step(&mut self, entity: &mut EntityBase, delta: Time) {
+  fn step(&mut self, context: &mut globals::Context, view: &mut screen::GameView, entity: &mut EntityBase, delta: Time) {
     match self {
       PersonData::Base => {
         step_base_person(entity, delta);
       },
       PersonData::Sidewalk(sidewalk) => {
-        sidewalk.step(entity, delta);
+        sidewalk.step(context, view, entity, delta);
       },
       PersonData::Player(player) => {
-        player.step(entity, delta);
+        player.step(context, view, entity, delta);
+      }
+    }
+  }
+
+  fn draw(&self, context: &mut globals::Context, entity: &EntityBase) {
+    draw(context, entity);
+  }
+
+  fn damage(&mut self, _context: &globals::Context, entity: &mut EntityBase, amount: IScalar) {
+    if entity.stance == EntityStance::Dead {
+      return;
+    }
+
+    entity.health -= amount;
+    if entity.health <= 0 {
+      entity.speed = 0.;
+      entity.set_new_stance(EntityStance::Dead);
+    }
+  }
+
+  fn on_route_event(&mut self, _context: &mut globals::Context, _view: &mut screen::GameView, entity: &mut EntityBase, event: route::RouteEvent) {
+    // the level's route is over, back to walking around. navigation notices that on its own
+    if let PersonData::Sidewalk(ref sidewalk) = *self {
+      if event == route::RouteEvent::Finished && sidewalk.navigation.is_none() && entity.stance != EntityStance::Dead {
+        entity.speed = 0.;
+        entity.set_new_stance(EntityStance::Standing);
       }
     }
   }
 
-  fn draw(&self, entity: &EntityBase) {
-    draw(entity);
+  fn on_animation_finished(&mut self, _context: &mut globals::Context, entity: &mut EntityBase) {
+    if entity.stance == EntityStance::Punching || entity.stance == EntityStance::Shooting {
+      entity.set_new_stance(EntityStance::Standing);
+    }
   }
 
   fn despawn_action(&mut self, entity: &mut EntityBase) -> bool {
@@ -252,9 +563,8 @@ impl EntityData for PersonData {
     }
   }
 
-  fn spawn(&mut self, _entity: &mut EntityBase, pos: Vec3f) -> Option<Vec3f> {
-    let game = globals::get_game();
-    if level::pos_is_sidewalk(&game.level, pos) {
+  fn spawn(&mut self, _context: &mut globals::Context, view: &screen::GameView, _entity: &mut EntityBase, pos: Vec3f) -> Option<Vec3f> {
+    if level::pos_is_sidewalk(view.level, pos) {
       Some(pos)
     } else {
       None
@@ -0,0 +1,151 @@
+use super::*;
+use dialog::Widget;
+
+// not in the original game, which draws everything in a fixed order
+
+// drawn in this order
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+  Ground,
+  Shadows,
+  // objects and entities, sorted by their y position
+  World,
+  Overhead,
+  Effects,
+  // these are drawn without the camera's transform
+  Hud,
+  Dialogs,
+  Console
+}
+
+impl Layer {
+  pub fn is_screen_space(&self) -> bool {
+    *self >= Layer::Hud
+  }
+}
+
+// entities don't have a single sprite, this is around their position
+const ENTITY_EXTENT: IScalar = 48;
+
+pub enum Drawable<'a> {
+  Sprite {
+    sprite: SpriteId,
+    pos: Vec3i,
+    flip: Flip
+  },
+  Entity(&'a entity::Entity),
+  Chunks(&'a mut level::ChunkCache, level::ChunkSource<'a>),
+  Skidmarks(&'a vehicle::Skidmarks),
+  Particles(&'a particle::Particles),
+  Dialog(&'a dialog::Dialog),
+  Console(&'a console::Console),
+  Custom(Box<Fn(&mut globals::Context) + 'a>)
+}
+
+impl<'a> Drawable<'a> {
+  fn draw(self, context: &mut globals::Context) {
+    match self {
+      Drawable::Sprite { sprite, pos, flip } => sprite::draw_sprite(context, sprite, pos, flip),
+      Drawable::Entity(entity) => entity.draw(context),
+      Drawable::Chunks(chunks, source) => chunks.draw(context, source),
+      Drawable::Skidmarks(skidmarks) => skidmarks.draw(context),
+      Drawable::Particles(particles) => particles.draw(context),
+      Drawable::Dialog(dialog) => dialog.draw(context, Vec3i::default()),
+      Drawable::Console(console) => console.draw(context),
+      Drawable::Custom(draw) => draw(context)
+    }
+  }
+}
+
+struct RenderItem<'a> {
+  layer: Layer,
+  sort_order: IScalar,
+  drawable: Drawable<'a>
+}
+
+// the part of the level on the screen, as [x1, y1, x2, y2]
+pub fn get_view_aabb(context: &globals::Context) -> [IScalar; 4] {
+  let size = viewport::get_screen_size(context);
+  let scale = 1. / context.platform.get_scale();
+  let start = context.platform.get_translation() * -1;
+
+  [start.x, start.y,
+   start.x + util::iscale_ceil(scale, size.x), start.y + util::iscale_ceil(scale, size.y)]
+}
+
+pub fn aabb_overlaps(a: [IScalar; 4], b: [IScalar; 4]) -> bool {
+  a[0] < b[2] && b[0] < a[2] && a[1] < b[3] && b[1] < a[3]
+}
+
+// collected every frame, culled against the view when added.
+// items on the same layer are drawn by sort order, then in the order they were added
+pub struct RenderQueue<'a> {
+  view: [IScalar; 4],
+  items: Vec<RenderItem<'a>>
+}
+
+impl<'a> RenderQueue<'a> {
+  // the camera's transform has to be set already
+  pub fn new(context: &globals::Context) -> Self {
+    RenderQueue {
+      view: get_view_aabb(context),
+      items: vec![]
+    }
+  }
+
+  pub fn push(&mut self, layer: Layer, drawable: Drawable<'a>) {
+    self.push_sorted(layer, 0, drawable);
+  }
+
+  pub fn push_sorted(&mut self, layer: Layer, sort_order: IScalar, drawable: Drawable<'a>) {
+    self.items.push(RenderItem {
+      layer,
+      sort_order,
+      drawable
+    });
+  }
+
+  // returns false if it's outside the view
+  pub fn push_culled(&mut self, layer: Layer, sort_order: IScalar, aabb: [IScalar; 4], drawable: Drawable<'a>) -> bool {
+    if !layer.is_screen_space() && !aabb_overlaps(aabb, self.view) {
+      return false;
+    }
+
+    self.push_sorted(layer, sort_order, drawable);
+    true
+  }
+
+  pub fn push_sprite(&mut self, context: &globals::Context, layer: Layer, sort_order: IScalar, sprite: SpriteId, pos: Vec3i, flip: Flip) -> bool {
+    let aabb = sprite::calc_aabb(&context.data.sprites[sprite as usize], pos, flip);
+    let aabb = [aabb[0] as IScalar, aabb[1] as IScalar, aabb[2] as IScalar, aabb[3] as IScalar];
+
+    self.push_culled(layer, sort_order, aabb, Drawable::Sprite { sprite, pos, flip })
+  }
+
+  pub fn push_entity(&mut self, context: &globals::Context, entity: &'a entity::Entity) -> bool {
+    if entity.base.hidden {
+      return false;
+    }
+
+    let pos = Vec3i::from(entity.base.get_draw_pos(context));
+    let aabb = [pos.x - ENTITY_EXTENT, pos.y - ENTITY_EXTENT, pos.x + ENTITY_EXTENT, pos.y + ENTITY_EXTENT];
+
+    self.push_culled(Layer::World, entity.base.sort_order, aabb, Drawable::Entity(entity))
+  }
+
+  pub fn draw(self, context: &mut globals::Context) {
+    let mut items = self.items;
+    // stable, so equal items keep the order they were added in
+    items.sort_by_key(|item| (item.layer, item.sort_order));
+
+    let mut screen_space = false;
+    for item in items.into_iter() {
+      if item.layer.is_screen_space() && !screen_space {
+        context.platform.reset();
+        screen_space = true;
+      }
+
+      item.drawable.draw(context);
+    }
+  }
+}
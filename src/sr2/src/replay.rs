@@ -0,0 +1,223 @@
+use opensrme_common::*;
+use super::*;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use input::{InputContext, InputKey};
+use std::io;
+use std::io::Write;
+
+// Per-frame input snapshots, used for reproducible bug reports and regression runs.
+//
+// header:
+//   "SR2R", version: u16, levelid: i32, seed: u64
+// frame:
+//   delta: u64
+//   keys: u8, [InputKey: i16, Time: u64]
+//   key_delta: u8, [InputKey: i16, pressed: u8]
+//   buttons: u8, [MouseButton: u8, Time: u64]
+//   button_delta: u8, [MouseButton: u8, pressed: u8]
+//   mouse: i32 * 2, mouse_delta: i32 * 2, mouse_scroll: i32
+//   left_stick: f64 * 2, right_stick: f64 * 2, using_controller: u8
+
+const REPLAY_MAGIC: &[u8; 4] = b"SR2R";
+const REPLAY_VERSION: u16 = 2;
+
+fn invalid_data(message: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn mouse_button_to_u8(button: MouseButton) -> u8 {
+  match button {
+    MouseButton::Unknown => 0,
+    MouseButton::Left    => 1,
+    MouseButton::Right   => 2,
+    MouseButton::Middle  => 3
+  }
+}
+
+fn u8_to_mouse_button(value: u8) -> MouseButton {
+  match value {
+    1 => MouseButton::Left,
+    2 => MouseButton::Right,
+    3 => MouseButton::Middle,
+    _ => MouseButton::Unknown
+  }
+}
+
+fn write_vec3i<T: Write>(file: &mut T, vec: Vec3i) -> io::Result<()> {
+  file.write_i32::<BigEndian>(vec.x)?;
+  file.write_i32::<BigEndian>(vec.y)
+}
+
+fn read_vec3i<T: io::Read>(file: &mut T) -> io::Result<Vec3i> {
+  let x = file.read_i32::<BigEndian>()?;
+  let y = file.read_i32::<BigEndian>()?;
+  Ok(Vec3i::new2(x, y))
+}
+
+fn write_vec3f<T: Write>(file: &mut T, vec: Vec3f) -> io::Result<()> {
+  file.write_f64::<BigEndian>(vec.x)?;
+  file.write_f64::<BigEndian>(vec.y)
+}
+
+fn read_vec3f<T: io::Read>(file: &mut T) -> io::Result<Vec3f> {
+  let x = file.read_f64::<BigEndian>()?;
+  let y = file.read_f64::<BigEndian>()?;
+  Ok(Vec3f::new2(x, y))
+}
+
+fn write_input<T: Write>(file: &mut T, input: &InputContext) -> io::Result<()> {
+  file.write_u8(input.keys.len() as u8)?;
+  for (key, time) in input.keys.iter() {
+    file.write_i16::<BigEndian>(*key as i16)?;
+    file.write_u64::<BigEndian>(*time)?;
+  }
+
+  file.write_u8(input.key_delta.len() as u8)?;
+  for (key, pressed) in input.key_delta.iter() {
+    file.write_i16::<BigEndian>(*key as i16)?;
+    file.write_u8(*pressed as u8)?;
+  }
+
+  file.write_u8(input.buttons.len() as u8)?;
+  for (button, time) in input.buttons.iter() {
+    file.write_u8(mouse_button_to_u8(*button))?;
+    file.write_u64::<BigEndian>(*time)?;
+  }
+
+  file.write_u8(input.button_delta.len() as u8)?;
+  for (button, pressed) in input.button_delta.iter() {
+    file.write_u8(mouse_button_to_u8(*button))?;
+    file.write_u8(*pressed as u8)?;
+  }
+
+  write_vec3i(file, input.mouse)?;
+  write_vec3i(file, input.mouse_delta)?;
+  file.write_i32::<BigEndian>(input.mouse_scroll)?;
+
+  write_vec3f(file, input.left_stick)?;
+  write_vec3f(file, input.right_stick)?;
+  file.write_u8(input.using_controller as u8)
+}
+
+fn read_inputkey<T: io::Read>(file: &mut T) -> io::Result<InputKey> {
+  let id = file.read_i16::<BigEndian>()?;
+
+  match InputKey::from_id(id) {
+    Some(key) => Ok(key),
+    None => Err(invalid_data("Invalid input key in replay"))
+  }
+}
+
+fn read_input<T: io::Read>(file: &mut T, input: &mut InputContext) -> io::Result<()> {
+  input.keys.clear();
+  for _i in 0..file.read_u8()? {
+    let key = read_inputkey(file)?;
+    input.keys.insert(key, file.read_u64::<BigEndian>()?);
+  }
+
+  input.key_delta.clear();
+  for _i in 0..file.read_u8()? {
+    let key = read_inputkey(file)?;
+    input.key_delta.insert(key, file.read_u8()? != 0);
+  }
+
+  input.buttons.clear();
+  for _i in 0..file.read_u8()? {
+    let button = u8_to_mouse_button(file.read_u8()?);
+    input.buttons.insert(button, file.read_u64::<BigEndian>()?);
+  }
+
+  input.button_delta.clear();
+  for _i in 0..file.read_u8()? {
+    let button = u8_to_mouse_button(file.read_u8()?);
+    input.button_delta.insert(button, file.read_u8()? != 0);
+  }
+
+  input.mouse = read_vec3i(file)?;
+  input.mouse_delta = read_vec3i(file)?;
+  input.mouse_scroll = file.read_i32::<BigEndian>()?;
+
+  input.left_stick = read_vec3f(file)?;
+  input.right_stick = read_vec3f(file)?;
+  input.using_controller = file.read_u8()? != 0;
+
+  Ok(())
+}
+
+pub struct InputRecorder {
+  file: io::BufWriter<std::fs::File>
+}
+
+impl InputRecorder {
+  pub fn new(path: &str, levelid: LevelId, seed: u64) -> io::Result<Self> {
+    let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+
+    file.write_all(REPLAY_MAGIC)?;
+    file.write_u16::<BigEndian>(REPLAY_VERSION)?;
+    file.write_i32::<BigEndian>(levelid)?;
+    file.write_u64::<BigEndian>(seed)?;
+
+    Ok(InputRecorder {
+      file
+    })
+  }
+
+  pub fn record_frame(&mut self, delta: Time, input: &InputContext) -> io::Result<()> {
+    self.file.write_u64::<BigEndian>(delta)?;
+    write_input(&mut self.file, input)
+  }
+
+  pub fn finish(&mut self) -> io::Result<()> {
+    self.file.flush()
+  }
+}
+
+pub struct InputReplay {
+  file: io::Cursor<Vec<u8>>,
+  pub levelid: LevelId,
+  pub seed: u64
+}
+
+impl InputReplay {
+  pub fn new(path: &str) -> io::Result<Self> {
+    let mut file = io::Cursor::new(std::fs::read(path)?);
+
+    let magic = file.read_amount_as_u8(4)?;
+    if &magic[..] != &REPLAY_MAGIC[..] {
+      return Err(invalid_data("Not a replay file"));
+    }
+
+    if file.read_u16::<BigEndian>()? != REPLAY_VERSION {
+      return Err(invalid_data("Unsupported replay version"));
+    }
+
+    let levelid = file.read_i32::<BigEndian>()?;
+    let seed = file.read_u64::<BigEndian>()?;
+
+    Ok(InputReplay {
+      file,
+      levelid,
+      seed
+    })
+  }
+
+  // returns the delta of the frame, or None once the replay has finished
+  pub fn read_frame(&mut self, input: &mut InputContext) -> Option<Time> {
+    if self.file.position() >= self.file.get_ref().len() as u64 {
+      return None;
+    }
+
+    let delta = match self.file.read_u64::<BigEndian>() {
+      Ok(delta) => delta,
+      Err(_) => return None
+    };
+
+    match read_input(&mut self.file, input) {
+      Ok(_) => Some(delta),
+      Err(error) => {
+        println!("Truncated replay: {}", error);
+        None
+      }
+    }
+  }
+}
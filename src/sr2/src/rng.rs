@@ -0,0 +1,123 @@
+use opensrme_common::*;
+
+const PCG_MULTIPLIER: u64 = 6364136223846793005;
+
+const GAMEPLAY_STREAM: u64 = 1;
+const COSMETIC_STREAM: u64 = 2;
+
+// PCG32 (XSH RR), small and fully deterministic across platforms
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Rng {
+  state: u64,
+  inc: u64
+}
+
+impl Rng {
+  pub fn new(seed: u64, stream: u64) -> Self {
+    let mut rng = Rng {
+      state: 0,
+      inc: (stream << 1) | 1
+    };
+
+    rng.next_u32();
+    rng.state = rng.state.wrapping_add(seed);
+    rng.next_u32();
+
+    rng
+  }
+
+  pub fn next_u32(&mut self) -> u32 {
+    let old = self.state;
+    self.state = old.wrapping_mul(PCG_MULTIPLIER).wrapping_add(self.inc);
+
+    let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+    let rot = (old >> 59) as u32;
+    xorshifted.rotate_right(rot)
+  }
+
+  // 0 <= result < n
+  pub fn pick_int(&mut self, n: IScalar) -> IScalar {
+    if n <= 0 {
+      return 0;
+    }
+
+    ((self.next_u32() as u64 * n as u64) >> 32) as IScalar
+  }
+
+  // 0 <= result < n
+  pub fn pick_float(&mut self, n: FScalar) -> FScalar {
+    if n <= 0. {
+      return 0.;
+    }
+
+    (self.next_u32() as FScalar / 4294967296.) * n
+  }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GameRng {
+  pub seed: u64,
+  // anything that changes the simulation (spawning, AI decisions)
+  pub gameplay: Rng,
+  // anything that only changes how things look (palettes, genders)
+  pub cosmetic: Rng
+}
+
+impl GameRng {
+  pub fn new(seed: u64) -> Self {
+    GameRng {
+      seed,
+      gameplay: Rng::new(seed, GAMEPLAY_STREAM),
+      cosmetic: Rng::new(seed, COSMETIC_STREAM)
+    }
+  }
+
+  pub fn new_random_seed() -> Self {
+    let seed = match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+      Ok(duration) => duration.as_secs() ^ ((duration.subsec_nanos() as u64) << 32),
+      Err(_) => 0
+    };
+
+    GameRng::new(seed)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn take_u32(rng: &mut Rng, count: usize) -> Vec<u32> {
+    (0..count).map(|_| rng.next_u32()).collect()
+  }
+
+  // replays depend on these never changing
+  #[test]
+  fn gameplay_sequence() {
+    let mut rng = GameRng::new(42);
+    assert_eq!(take_u32(&mut rng.gameplay, 4), vec![1307692281, 3850602322, 1491967504, 4091771729]);
+
+    let mut rng = GameRng::new(42);
+    let picks: Vec<IScalar> = (0..6).map(|_| rng.gameplay.pick_int(100)).collect();
+    assert_eq!(picks, vec![30, 89, 34, 95, 90, 41]);
+  }
+
+  #[test]
+  fn cosmetic_sequence() {
+    let mut rng = GameRng::new(42);
+    assert_eq!(take_u32(&mut rng.cosmetic, 4), vec![4286966985, 1306841846, 1928016788, 384721191]);
+
+    let mut rng = GameRng::new(42);
+    let picks: Vec<IScalar> = (0..6).map(|_| rng.cosmetic.pick_int(100)).collect();
+    assert_eq!(picks, vec![99, 30, 44, 8, 8, 68]);
+  }
+
+  #[test]
+  fn streams_are_independent() {
+    let mut rng = GameRng::new(42);
+    let cosmetic = take_u32(&mut rng.cosmetic, 16);
+
+    let mut rng = GameRng::new(42);
+    rng.gameplay.pick_int(10);
+    assert_eq!(take_u32(&mut rng.cosmetic, 16), cosmetic);
+  }
+}
@@ -1,4 +1,35 @@
 use super::*;
+use entity::EntityStance;
+
+// not in the original game, which doesn't use the byte stored with each node.
+// only read as events on routes that opted in, see Route::has_events.
+// the top 3 bits are the kind of event, the lower 5 bits its argument
+const EVENT_NONE: u8 = 0;
+const EVENT_WAIT: u8 = 1;
+const EVENT_STANCE: u8 = 2;
+const EVENT_TRIGGER: u8 = 3;
+const EVENT_WAIT_FOR_TRIGGER: u8 = 4;
+const EVENT_LOOP: u8 = 5;
+const EVENT_PING_PONG: u8 = 6;
+// in milliseconds per unit of the argument
+const WAIT_UNIT: Time = 250;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RouteEvent {
+  // stays at the node for a while
+  Wait(Time),
+  Stance(EntityStance),
+  // seen by every entity on the next step, see WaitForTrigger
+  Trigger(u8),
+  // stops at the node (entity flag 0x4000) until something sends the trigger
+  WaitForTrigger(u8),
+  // back to the first node
+  Loop,
+  // turns around, then again once back at the first node
+  PingPong,
+  // reached the end of the route
+  Finished
+}
 
 #[derive(Debug, Copy, Clone, PartialEq, Default)]
 pub struct RoutePart {
@@ -6,12 +37,31 @@ pub struct RoutePart {
   pub pos: Vec3f,
   // 3
   pub distance: f64,
-  pub unk1: u8
+  pub event: u8
+}
+
+impl RoutePart {
+  pub fn get_event(&self) -> Option<RouteEvent> {
+    let argument = self.event & 0x1f;
+
+    match self.event >> 5 {
+      EVENT_NONE => None,
+      EVENT_WAIT => Some(RouteEvent::Wait(argument as Time * WAIT_UNIT)),
+      EVENT_STANCE => entity::get_entitystance(argument as i32).map(RouteEvent::Stance),
+      EVENT_TRIGGER => Some(RouteEvent::Trigger(argument)),
+      EVENT_WAIT_FOR_TRIGGER => Some(RouteEvent::WaitForTrigger(argument)),
+      EVENT_LOOP => Some(RouteEvent::Loop),
+      EVENT_PING_PONG => Some(RouteEvent::PingPong),
+      _ => None
+    }
+  }
 }
 
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct Route {
-  pub parts: Vec<RoutePart>
+  pub parts: Vec<RoutePart>,
+  // the original levels have other values in the event bytes, which have to stay ignored
+  pub has_events: bool
 }
 
 impl Route {
@@ -64,6 +114,15 @@ pub struct RouteData {
   pub routeid: Option<RouteId>,
   pub threshold: FScalar,
   pub progress: FScalar,
+
+  // the last node that was passed
+  part: usize,
+  // going back towards the first node, for ping-pong routes
+  reversed: bool,
+  wait_millis: Time,
+  pub waiting_for: Option<u8>,
+  // for the entity to handle, see EntityData::on_route_event
+  pub events: Vec<RouteEvent>
 }
 
 impl RouteData {
@@ -71,48 +130,153 @@ impl RouteData {
     self.route = Some(route);
     self.threshold = threshold;
     self.progress = 0.;
+
+    self.part = 0;
+    self.reversed = false;
+    self.wait_millis = 0;
+    self.waiting_for = None;
   }
 
-  pub fn set_route_to_routeid(&mut self) -> bool {
+  pub fn set_route_to_routeid(&mut self, level: &Level) -> bool {
     if let Some(routeid) = self.routeid {
-      let game = globals::get_game();
-      self.set_route(game.level.routes[routeid as usize].clone(), 0.);
+      self.set_route(level.routes[routeid as usize].clone(), 0.);
       true
     } else {
       false
     }
   }
 
-  pub fn reset_route(&mut self, _do_events: bool) {
-    self.route = None;
-    // TODO: do event
+  pub fn reset_route(&mut self, do_events: bool) {
+    if self.route.take().is_some() && do_events {
+      self.events.push(RouteEvent::Finished);
+    }
+
+    self.wait_millis = 0;
+    self.waiting_for = None;
   }
 
-  fn get_total_distance(&self) -> Option<FScalar> {
-    if let Some(ref route) = self.route {
-      Some(route.get_total_distance())
-    } else {
-      None
+  pub fn take_events(&mut self) -> Vec<RouteEvent> {
+    std::mem::replace(&mut self.events, vec![])
+  }
+
+  // returns true if the entity has to stop at the node
+  fn do_event(&mut self, event: RouteEvent, route: &Route) -> bool {
+    match event {
+      RouteEvent::Wait(millis) => {
+        self.wait_millis = millis;
+      },
+      RouteEvent::WaitForTrigger(trigger) => {
+        self.waiting_for = Some(trigger);
+      },
+      RouteEvent::Loop => {
+        self.part = 0;
+        self.progress = 0.;
+      },
+      RouteEvent::PingPong => {
+        self.reversed = !self.reversed;
+      },
+      RouteEvent::Stance(_) | RouteEvent::Trigger(_) | RouteEvent::Finished => {}
+    }
+
+    self.events.push(event);
+
+    match event {
+      RouteEvent::Wait(_) | RouteEvent::WaitForTrigger(_) | RouteEvent::PingPong => {
+        self.progress = route.parts[self.part].distance;
+        true
+      },
+      RouteEvent::Loop => true,
+      _ => false
     }
   }
 
-  pub fn step(&mut self, speed: FScalar, delta: Time) -> Option<(Vec3f, Angle)> {
-    // TODO: return None if flag 0x4000 is not 0
-    if let Some(ref route) = self.route {
-      if (route.get_total_distance() - self.progress) > self.threshold {
-        self.progress += speed * (delta as FScalar / 1000.);
-        if let Some(angle) = route.get_angle_at_point(self.progress) {
-          return Some((
-            route.get_point(self.progress).unwrap(),
-            angle
-          ));
+  // fires the events of every node passed since the last step
+  fn pass_nodes(&mut self, route: &Route) {
+    loop {
+      let next = if self.reversed {
+        if self.part == 0 {
+          break;
+        }
+
+        self.part - 1
+      } else {
+        if self.part + 1 >= route.parts.len() {
+          break;
+        }
+
+        self.part + 1
+      };
+
+      let distance = route.parts[next].distance;
+      let reached = if self.reversed {
+        self.progress <= distance
+      } else {
+        self.progress >= distance
+      };
+
+      if !reached {
+        break;
+      }
+
+      self.part = next;
+
+      let event = if self.reversed && next == 0 {
+        // the way back is over, start again
+        Some(RouteEvent::PingPong)
+      } else if route.has_events {
+        route.parts[next].get_event()
+      } else {
+        None
+      };
+
+      if let Some(event) = event {
+        if self.do_event(event, route) {
+          break;
         }
       }
+    }
+  }
+
+  fn get_current(&self, route: &Route) -> Option<(Vec3f, Angle)> {
+    let point = route.get_point(self.progress)
+      .unwrap_or(route.parts[self.part].pos);
+    let angle = route.get_angle_at_point(self.progress)?;
+
+    if self.reversed {
+      Some((point, util::normalize_angle(angle + util::PI)))
     } else {
+      Some((point, angle))
+    }
+  }
+
+  pub fn step(&mut self, speed: FScalar, delta: Time) -> Option<(Vec3f, Angle)> {
+    let route = self.route.take()?;
+
+    let distance = speed * (delta as FScalar / 1000.);
+    let mut finished = false;
+
+    if self.waiting_for.is_some() {
+      // resumed by the entity once the trigger is seen
+    } else if self.wait_millis > 0 {
+      self.wait_millis = self.wait_millis.saturating_sub(delta);
+    } else if self.reversed {
+      self.progress = util::fmax(0., self.progress - distance);
+      self.pass_nodes(&route);
+    } else if (route.get_total_distance() - self.progress) > self.threshold {
+      self.progress += distance;
+      self.pass_nodes(&route);
+    } else {
+      finished = true;
+    }
+
+    let current = self.get_current(&route);
+    self.route = Some(route);
+
+    if finished || current.is_none() {
+      self.reset_route(true);
       return None;
     }
 
-    self.reset_route(true);
-    None
+    current
   }
 }
@@ -2,10 +2,55 @@ use super::*;
 use dialog::Widget;
 
 pub trait Screen {
-  fn init(&mut self) {}
-  fn step(&mut self, _delta: Time) {}
-  fn set_size(&mut self, _size: Vec3i) {}
-  fn draw(&mut self) {}
+  fn init(&mut self, _context: &mut globals::Context) {}
+  fn step(&mut self, _context: &mut globals::Context, _delta: Time) {}
+  // returns true if the event was consumed
+  fn input(&mut self, _context: &mut globals::Context, _event: Event) -> bool { false }
+  fn set_size(&mut self, _context: &mut globals::Context, _size: Vec3i) {}
+  // anything drawn into a render target has to be drawn again
+  fn render_targets_reset(&mut self, _context: &mut globals::Context) {}
+  fn draw(&mut self, _context: &mut globals::Context) {}
+}
+
+// What an entity can see of the GameScreen while it's being stepped or spawned.
+// The entity itself is split out of the entity list so it can be borrowed mutably.
+pub struct GameView<'a> {
+  pub level: &'a Level,
+  pub vehicle_state: &'a vehicle::VehicleState,
+  pub entity_spawn_counter: usize,
+  pub main_camera_pos: Vec3f,
+  pub scale: FScalar,
+  // None while the controller itself is running
+  pub playercontroller: Option<&'a mut Box<controller::PlayerController>>,
+  pub skidmarks: &'a mut vehicle::Skidmarks,
+  pub particles: &'a mut particle::Particles,
+  pub explosions: &'a mut Vec<vehicle::Explosion>,
+  // raised on the previous step
+  pub alarms: &'a [person::Alarm],
+  pub new_alarms: &'a mut Vec<person::Alarm>,
+  // sent by route events on the previous step
+  pub route_triggers: &'a [u8],
+  pub new_route_triggers: &'a mut Vec<u8>,
+  pub pathfinder: &'a mut pathfinding::Pathfinder,
+
+  entities_before: &'a [entity::Entity],
+  entities_after: &'a [entity::Entity]
+}
+
+impl<'a> GameView<'a> {
+  // every entity except for the one the view was created for
+  pub fn other_entities(&self) -> std::iter::Chain<std::slice::Iter<entity::Entity>, std::slice::Iter<entity::Entity>> {
+    self.entities_before.iter().chain(self.entities_after.iter())
+  }
+
+  pub fn screen_pos_to_game_pos(&self, screenpos: Vec3i) -> Vec3f {
+    screen_pos_to_game_pos(self.main_camera_pos, self.scale, screenpos)
+  }
+}
+
+// with a viewport, mouse positions are already mapped from the window to the screen
+fn screen_pos_to_game_pos(main_camera_pos: Vec3f, scale: FScalar, screenpos: Vec3i) -> Vec3f {
+  (Vec3f::from(screenpos) / scale) - main_camera_pos
 }
 
 //#[derive(Debug, Clone, PartialEq)]
@@ -13,76 +58,183 @@ pub struct GameScreen {
   pub level: Level,
   pub levelid: LevelId,
   pub playercontroller: Box<controller::PlayerController>,
+  pub control_scheme: controller::ControlScheme,
   pub entities: Vec<entity::Entity>,
-  pub entity_ids: Vec<EntityId>,
   pub main_camera_pos: Vec3f,
   pub camera: Camera,
   pub scale: FScalar,
 
   pub vehicle_state: vehicle::VehicleState,
   pub entity_spawn_counter: usize,
-
-  pub dialogs: Vec<dialog::Dialog>
+  pub skidmarks: vehicle::Skidmarks,
+  pub particles: particle::Particles,
+  explosions: Vec<vehicle::Explosion>,
+  alarms: Vec<person::Alarm>,
+  new_alarms: Vec<person::Alarm>,
+  route_triggers: Vec<u8>,
+  new_route_triggers: Vec<u8>,
+  pathfinder: pathfinding::Pathfinder,
+  // index of the vehicle the player is in, and what it was before the player got in
+  pub player_vehicle: Option<usize>,
+  player_vehicle_type: entity::EntityType,
+  collision_grid: collision::CollisionGrid,
+  layer1_chunks: level::ChunkCache,
+  shadow_chunks: level::ChunkCache,
+  layer2_chunks: level::ChunkCache,
+
+  pub dialogs: Vec<dialog::Dialog>,
+  pub debug_overlay: debug::DebugOverlay,
+  pub console: console::Console
 }
 
+// entities moving further than this in a single step are swept instead of only checked at the end
+const SWEEP_THRESHOLD: FScalar = (util::TILESIZE / 4) as FScalar;
+// people placed on a route by the level follow it this fast
+const ROUTE_SPEED: FScalar = 20.;
+// how close a swept object has to be to a wall to still count as touching it
+const SWEEP_CONTACT_MARGIN: FScalar = 0.5;
+
 struct CollisionResponse {
   response: Vec3f,
+  // from the first towards the second
+  normal: Vec3f,
   percent1: FScalar,
   percent2: FScalar
 }
 
 impl GameScreen {
-  pub fn new(levelid: LevelId) -> Self {
-    let context = globals::get_context();
-
+  pub fn new(context: &mut globals::Context, levelid: LevelId) -> Self {
     let mut game = GameScreen {
-      level: level::get_level_from_levelid(levelid),
+      level: level::get_level_from_levelid(context, levelid),
       levelid,
-      playercontroller: Box::new(controller::ModernPlayerControls::new()),
+      playercontroller: controller::ControlScheme::Modern.create_controller(),
+      control_scheme: controller::ControlScheme::Modern,
       entities: vec![],
-      entity_ids: vec![],
       main_camera_pos: Vec3f::default(),
       camera: Camera::default(),
       scale: 1.,
 
       vehicle_state: vehicle::VehicleState::new(),
       entity_spawn_counter: 0,
-
-      dialogs: vec![]
+      skidmarks: vehicle::Skidmarks::new(),
+      particles: particle::Particles::new(),
+      explosions: vec![],
+      alarms: vec![],
+      new_alarms: vec![],
+      route_triggers: vec![],
+      new_route_triggers: vec![],
+      pathfinder: pathfinding::Pathfinder::new(),
+      player_vehicle: None,
+      player_vehicle_type: entity::EntityType::Type8,
+      collision_grid: collision::CollisionGrid::new(),
+      layer1_chunks: level::ChunkCache::new(),
+      shadow_chunks: level::ChunkCache::new(),
+      layer2_chunks: level::ChunkCache::new(),
+
+      dialogs: vec![],
+      debug_overlay: debug::DebugOverlay::new(),
+      console: console::Console::new()
     };
 
     game.camera.size = Vec3i::new2(240, 320);
 
-    game.dialogs.push(dialog::Dialog::new(Box::new(dialog::PauseMenu::new())));
-    game.dialogs[0].set_boundaries(dialog::SizeBoundary::Both(context.platform.get_size()));
+    let pausemenu = dialog::PauseMenu::new(context);
+    game.dialogs.push(dialog::Dialog::new(context, Box::new(pausemenu)));
+    game.dialogs[0].set_boundaries(context, dialog::SizeBoundary::Both(viewport::get_screen_size(context)));
 
     game
   }
 
-  fn handle_collision(entity1: &mut entity::Entity, mut entity2: Option<&mut entity::Entity>, response: CollisionResponse) {
-    //entity1.on_collision(entity2, response);
-    if let Some(ref mut entity2) = entity2 {
-      let end_pos = entity1.base.pos - (response.response * response.percent1);
+  // splits the entity out of the rest of the game
+  fn get_entity_view(&mut self, index: usize) -> (&mut entity::Entity, GameView) {
+    let (entities_before, rest) = self.entities.split_at_mut(index);
+    let (entity, entities_after) = rest.split_first_mut().unwrap();
+
+    (entity, GameView {
+      level: &self.level,
+      vehicle_state: &self.vehicle_state,
+      entity_spawn_counter: self.entity_spawn_counter,
+      main_camera_pos: self.main_camera_pos,
+      scale: self.scale,
+      playercontroller: Some(&mut self.playercontroller),
+      skidmarks: &mut self.skidmarks,
+      particles: &mut self.particles,
+      explosions: &mut self.explosions,
+      alarms: &self.alarms,
+      new_alarms: &mut self.new_alarms,
+      route_triggers: &self.route_triggers,
+      new_route_triggers: &mut self.new_route_triggers,
+      pathfinder: &mut self.pathfinder,
+      entities_before,
+      entities_after
+    })
+  }
+
+  // how fast the entity moved this step, in units per second
+  fn get_velocity(entity: &entity::Entity, delta: Time) -> Vec3f {
+    if delta <= 0 {
+      return Vec3f::default();
+    }
+
+    (entity.base.pos - entity.base.prev_pos) * (1000. / delta as FScalar)
+  }
+
+  // the velocities are updated, so touching more than one thing doesn't stop an entity twice
+  fn handle_collision(entity1: &mut entity::Entity, velocity1: &mut Vec3f,
+                      mut entity2: Option<(&mut entity::Entity, &mut Vec3f)>, response: CollisionResponse) {
+    let velocity2 = match entity2 {
+      Some((_, ref velocity2)) => **velocity2,
+      None => Vec3f::default()
+    };
+
+    // inelastic, the speed they're closing in at is taken away, split by weight like the push
+    let closing_speed = util::vec_dot(*velocity1 - velocity2, response.normal);
+    let impact = if closing_speed > 0. {
+      response.normal * closing_speed
+    } else {
+      Vec3f::default()
+    };
+
+    if let Some((ref mut entity2, ref mut velocity2)) = entity2 {
+      let push1 = response.response * -response.percent1;
+      let impact1 = impact * -response.percent1;
+      let end_pos = entity1.base.pos + push1;
       entity1.set_pos(end_pos);
+      entity1.on_collision(push1, impact1);
+      *velocity1 = *velocity1 + impact1;
 
-      let end_pos = entity2.base.pos + (response.response * response.percent2);
+      let push2 = response.response * response.percent2;
+      let impact2 = impact * response.percent2;
+      let end_pos = entity2.base.pos + push2;
       entity2.set_pos(end_pos);
+      entity2.on_collision(push2, impact2);
+      **velocity2 = **velocity2 + impact2;
     } else {
-      let end_pos = entity1.base.pos - response.response;
+      let push = response.response * -1.;
+      let end_pos = entity1.base.pos + push;
       entity1.set_pos(end_pos);
+      entity1.on_collision(push, impact * -1.);
+      *velocity1 = *velocity1 - impact;
     }
   }
 
+  // the weights are inverse masses, so the lighter one is moved further
+  fn get_weight_split(collision1: &collision::PhysicalObject, collision2: &collision::PhysicalObject) -> (FScalar, FScalar) {
+    let total_weight = collision1.weight + collision2.weight;
+    (collision1.weight / total_weight, collision2.weight / total_weight)
+  }
+
   fn get_entity_response(entity1: &entity::Entity, entity2: &entity::Entity) -> Option<CollisionResponse> {
     if let Some(ref collision1) = entity1.collision {
       if let Some(ref collision2) = entity2.collision {
         let response = collision1.get_response_vector(collision2);
         if let Some(response) = response {
-          let total_weight = collision1.weight + collision2.weight;
-          let percent1 = collision1.weight / total_weight;
-          let percent2 = collision2.weight / total_weight;
+          let length = response.len2();
+          let normal = if length > 0. { response / length } else { Vec3f::default() };
+          let (percent1, percent2) = GameScreen::get_weight_split(collision1, collision2);
           return Some(CollisionResponse {
             response,
+            normal,
             percent1,
             percent2
           });
@@ -100,21 +252,15 @@ impl GameScreen {
     })
   }
 
-  fn check_tile_collision_inner(level: &Level, entity: &mut entity::Entity, collision: collision::PhysicalObject) -> bool {
+  // impassable tiles touching the area between min and max
+  fn get_wall_objects(level: &Level, min: Vec3f, max: Vec3f) -> Vec<collision::PhysicalObject> {
     // TODO: make static
     let tile = GameScreen::create_tile_object();
 
-    let radius = match collision.shape {
-      collision::Shape::Circle(radius) => radius,
-      collision::Shape::Rect(vec) => vec.len2().ceil() as IScalar
-    };
-
-    let pos = collision.pos();
-
-    let start = level::pos_to_tilepos(pos - radius as FScalar);
-    let end   = level::pos_to_tilepos(pos + radius as FScalar);
+    let start = level::pos_to_tilepos(min);
+    let end   = level::pos_to_tilepos(max);
 
-    let mut ret = false;
+    let mut walls = vec![];
 
     for x in start.x..=end.x {
       for y in start.y..=end.y {
@@ -124,24 +270,98 @@ impl GameScreen {
           tile_gamepos.x += (util::TILESIZE / 2) as FScalar;
           tile_gamepos.y += (util::TILESIZE / 2) as FScalar;
 
-          let newtile = tile.clone_with_pa(tile_gamepos, 0.);
-          if let Some(response) = collision.get_response_vector(&newtile) {
-            GameScreen::handle_collision(entity, None, CollisionResponse {
-              response,
-              percent1: 1.,
-              percent2: 0.
-            });
-
-            ret = true;
-          }
+          walls.push(tile.clone_with_pa(tile_gamepos, 0.));
         }
       }
     }
 
+    walls
+  }
+
+  fn check_tile_collision_inner(level: &Level, entity: &mut entity::Entity, velocity: &mut Vec3f, collision: collision::PhysicalObject) -> bool {
+    let radius = collision.get_radius() as FScalar;
+    let pos = collision.pos();
+
+    let mut ret = false;
+
+    for wall in GameScreen::get_wall_objects(level, pos - radius, pos + radius).iter() {
+      if let Some(response) = collision.get_response_vector(wall) {
+        let length = response.len2();
+        GameScreen::handle_collision(entity, velocity, None, CollisionResponse {
+          response,
+          normal: if length > 0. { response / length } else { Vec3f::default() },
+          percent1: 1.,
+          percent2: 0.
+        });
+
+        ret = true;
+      }
+    }
+
     ret
   }
 
-  fn check_tile_collision(level: &Level, entity: &mut entity::Entity) -> bool {
+  // stops the entity at the first wall it hits between its previous and current position,
+  // the rest of the movement slides along the wall
+  fn sweep_tile_collision(level: &Level, entity: &mut entity::Entity, velocity: &mut Vec3f, collision: &collision::PhysicalObject) -> bool {
+    let prevpos = entity.base.prev_pos;
+    let pos = entity.base.pos;
+    let movement = pos - prevpos;
+
+    let start = collision.clone_with_pa(prevpos, entity.base.prev_angle);
+    let radius = collision.get_radius() as FScalar;
+
+    let min = Vec3f::new2(util::fmin(prevpos.x, pos.x), util::fmin(prevpos.y, pos.y)) - radius;
+    let max = Vec3f::new2(util::fmax(prevpos.x, pos.x), util::fmax(prevpos.y, pos.y)) + radius;
+
+    let mut first_hit: Option<(FScalar, Vec3f)> = None;
+
+    for wall in GameScreen::get_wall_objects(level, min, max).iter() {
+      let toi = match start.get_time_of_impact(movement, wall, Vec3f::default()) {
+        Some(toi) => toi,
+        None => continue
+      };
+
+      if let Some((first_toi, _)) = first_hit {
+        if first_toi <= toi {
+          continue;
+        }
+      }
+
+      let hit = start.clone_with_pa(prevpos + movement * toi, entity.base.prev_angle);
+      let normal = match hit.get_contact_normal(wall, SWEEP_CONTACT_MARGIN) {
+        Some(normal) => normal,
+        None => continue
+      };
+
+      // already touching at the start, but moving away from the wall
+      if util::vec_dot(movement, normal) <= 0. {
+        continue;
+      }
+
+      first_hit = Some((toi, normal));
+    }
+
+    if let Some((toi, normal)) = first_hit {
+      let remaining = movement * (1. - toi);
+      let slide = remaining - normal * util::vec_dot(remaining, normal);
+
+      // walls don't move, everything going into them is stopped
+      let impact = normal * -util::fmax(0., util::vec_dot(*velocity, normal));
+
+      entity.rewind(toi);
+      let end_pos = entity.base.pos + slide;
+      entity.set_pos(end_pos);
+      entity.on_collision(end_pos - pos, impact);
+      *velocity = *velocity + impact;
+
+      true
+    } else {
+      false
+    }
+  }
+
+  fn check_tile_collision(level: &Level, entity: &mut entity::Entity, velocity: &mut Vec3f) -> bool {
     let collision = if let Some(ref collision1) = entity.collision {
       // clone to avoid reference problems
       collision1.clone()
@@ -149,58 +369,162 @@ impl GameScreen {
       return false;
     };
 
-    let prevpos = entity.base.prev_pos;
-    let pos = entity.base.pos;
-    let diff = pos - prevpos;
-    let amount = (diff.abs().max2() as IScalar) / (util::TILESIZE / 4);
-
-    if amount > 1 {
-      for i in 0..amount {
-        // TODO: interpolate between angles? not in original game
-        let newcollision = collision.clone_with_pa(entity.base.pos + (diff * i as FScalar) / amount as FScalar, entity.base.angle);
-        if GameScreen::check_tile_collision_inner(level, entity, newcollision) {
-          return true;
-        }
+    let movement = entity.base.pos - entity.base.prev_pos;
+
+    if movement.abs().max2() > SWEEP_THRESHOLD &&
+      GameScreen::sweep_tile_collision(level, entity, velocity, &collision) {
+        // the entity was moved, the slide can still end up inside a wall
+        let collision = collision.clone_with_isometry(collision::PhysicalObject::get_isometry(&entity.base));
+        GameScreen::check_tile_collision_inner(level, entity, velocity, collision);
+        return true;
       }
+
+    return GameScreen::check_tile_collision_inner(level, entity, velocity, collision);
+  }
+
+  // rewinds both entities to where they first touched if they passed through each other this step.
+  // only the movement is swept, both keep their previous angle until rewind() interpolates it
+  fn sweep_entity_collision(entity1: &mut entity::Entity, entity2: &mut entity::Entity) -> Option<CollisionResponse> {
+    let movement1 = entity1.base.pos - entity1.base.prev_pos;
+    let movement2 = entity2.base.pos - entity2.base.prev_pos;
+
+    if movement1.abs().max2() <= SWEEP_THRESHOLD && movement2.abs().max2() <= SWEEP_THRESHOLD {
+      return None;
     }
 
-    return GameScreen::check_tile_collision_inner(level, entity, collision);
+    let hit = if let (Some(ref collision1), Some(ref collision2)) = (&entity1.collision, &entity2.collision) {
+      let start1 = collision1.clone_with_pa(entity1.base.prev_pos, entity1.base.prev_angle);
+      let start2 = collision2.clone_with_pa(entity2.base.prev_pos, entity2.base.prev_angle);
+
+      match start1.get_time_of_impact(movement1, &start2, movement2) {
+        // already overlapping at the start, that's left to the normal response
+        Some(toi) if toi <= 0. && start1.collides_with(&start2) => None,
+        Some(toi) => {
+          let hit1 = collision1.clone_with_pa(entity1.base.prev_pos + movement1 * toi, entity1.base.prev_angle);
+          let hit2 = collision2.clone_with_pa(entity2.base.prev_pos + movement2 * toi, entity2.base.prev_angle);
+
+          match hit1.get_contact_normal(&hit2, SWEEP_CONTACT_MARGIN) {
+            // moving apart, like the wall sweep
+            Some(normal) if util::vec_dot(movement1 - movement2, normal) <= 0. => None,
+            Some(normal) => Some((toi, normal, GameScreen::get_weight_split(collision1, collision2))),
+            None => None
+          }
+        },
+        None => None
+      }
+    } else {
+      None
+    };
+
+    let (toi, normal, (percent1, percent2)) = hit?;
+    entity1.rewind(toi);
+    entity2.rewind(toi);
+
+    // usually only touching now, with nothing to push apart, but they still hit each other
+    Some(GameScreen::get_entity_response(entity1, entity2).unwrap_or(CollisionResponse {
+      response: Vec3f::default(),
+      normal,
+      percent1,
+      percent2
+    }))
   }
 
-  fn step_collision(&mut self, _delta: Time) {
+  fn update_collision_grid(&mut self) {
+    self.collision_grid.clear();
+
+    for (id, entity) in self.entities.iter().enumerate() {
+      if !entity.is_physical() {
+        continue;
+      }
+
+      if let Some(ref collision) = entity.collision {
+        self.collision_grid.insert(id, collision, entity.base.prev_pos);
+      }
+    }
+  }
+
+  fn step_collision(&mut self, delta: Time) {
+    self.update_collision_grid();
+    let pairs = self.collision_grid.get_pairs();
+
+    self.step_collision_pairs(&pairs, delta);
+  }
+
+  fn step_collision_pairs(&mut self, pairs: &[(usize, usize)], delta: Time) {
     let entities_len = self.entities.len();
+    let mut pair_id = 0;
+
+    // from before anything was pushed, collisions only take away the part going into each other
+    let mut velocities: Vec<Vec3f> = self.entities.iter().map(|entity| GameScreen::get_velocity(entity, delta)).collect();
 
     for entity1_id in 0..entities_len {
       //let entity1 = &mut self.entities[entity1_id];
 
+      // pairs are sorted by the first entity, skip past the ones of entities before this,
+      // including any that stopped being physical since the grid was built
+      while pair_id < pairs.len() && pairs[pair_id].0 < entity1_id {
+        pair_id += 1;
+      }
+
       if !self.entities[entity1_id].is_physical() {
         continue;
       }
 
-      for entity2_id in entity1_id+1..entities_len {
+      while pair_id < pairs.len() && pairs[pair_id].0 == entity1_id {
+        let entity2_id = pairs[pair_id].1;
+        pair_id += 1;
+
         if !self.entities[entity2_id].is_physical() {
           continue;
         }
 
-        if let Some(response) = GameScreen::get_entity_response(&self.entities[entity1_id],
-                                                                &self.entities[entity2_id]) {
-          let mut splitted = self.entities[..].split_at_mut(entity1_id + 1);
-          let entity1 = &mut splitted.0[entity1_id];
-          let entity2 = &mut splitted.1[entity2_id - entity1_id - 1];
-          GameScreen::handle_collision(entity1, Some(entity2), response);
+        let mut splitted = self.entities[..].split_at_mut(entity1_id + 1);
+        let entity1 = &mut splitted.0[entity1_id];
+        let entity2 = &mut splitted.1[entity2_id - entity1_id - 1];
+
+        let splitted = velocities[..].split_at_mut(entity1_id + 1);
+        let velocity1 = &mut splitted.0[entity1_id];
+        let velocity2 = &mut splitted.1[entity2_id - entity1_id - 1];
+
+        let mut response = GameScreen::get_entity_response(entity1, entity2);
+        if response.is_none() {
+          response = GameScreen::sweep_entity_collision(entity1, entity2);
+        }
+
+        if let Some(response) = response {
+          GameScreen::handle_collision(entity1, velocity1, Some((entity2, velocity2)), response);
         }
       }
 
-      GameScreen::check_tile_collision(&self.level, &mut self.entities[entity1_id]);
+      GameScreen::check_tile_collision(&self.level, &mut self.entities[entity1_id], &mut velocities[entity1_id]);
     }
   }
 
   pub fn screen_pos_to_game_pos(&self, screenpos: Vec3i) -> Vec3f {
-    (Vec3f::from(screenpos) / self.scale) - self.main_camera_pos
+    screen_pos_to_game_pos(self.main_camera_pos, self.scale, screenpos)
+  }
+
+  pub fn open_dialog(&mut self, context: &mut globals::Context, widget: Box<Widget>) {
+    let mut dialog = dialog::Dialog::new(context, widget);
+    dialog.set_boundaries(context, dialog::SizeBoundary::Both(viewport::get_screen_size(context)));
+    self.dialogs.push(dialog);
+
+    // the dialog will consume the release events
+    context.input.release_all();
+  }
+
+  pub fn set_control_scheme(&mut self, scheme: controller::ControlScheme) {
+    println!("Using {:?} controls", scheme);
+
+    self.control_scheme = scheme;
+    self.playercontroller = scheme.create_controller();
   }
 
-  fn process_input(&mut self) {
-    let context = globals::get_context();
+  fn process_input(&mut self, context: &mut globals::Context) {
+    let mut open_bindings_menu = false;
+    let mut toggle_controls = false;
+    let mut toggle_vehicle = false;
+    let mut open_console = false;
 
     for key in context.input.key_delta.iter() {
       if !key.1 {
@@ -212,10 +536,48 @@ impl GameScreen {
           context.running = false;
           break;
         },
+        input::InputKey::Rebind => {
+          open_bindings_menu = true;
+        },
+        input::InputKey::ToggleControls => {
+          toggle_controls = true;
+        },
+        input::InputKey::Vehicle => {
+          toggle_vehicle = true;
+        },
+        input::InputKey::ToggleDebug => {
+          self.debug_overlay.toggle();
+        },
+        input::InputKey::Console => {
+          open_console = true;
+        },
         _ => {}
       }
     }
 
+    if open_bindings_menu && context.recording_input {
+      println!("The bindings can't be changed while recording or playing back a replay");
+    } else if open_bindings_menu {
+      self.open_dialog(context, Box::new(dialog::BindingsMenu::new()));
+    }
+
+    if toggle_controls {
+      let scheme = self.control_scheme.next();
+      self.set_control_scheme(scheme);
+    }
+
+    if toggle_vehicle {
+      self.toggle_player_vehicle(context);
+    }
+
+    if open_console && context.recording_input {
+      println!("The console can't be used while recording or playing back a replay");
+    } else if open_console {
+      self.console.open = true;
+      // the console gets the release events
+      context.input.release_all();
+    }
+
     if context.input.buttons.get(&MouseButton::Left).is_some() {
       self.main_camera_pos = self.main_camera_pos + Vec3f::from(context.input.mouse_delta) / self.scale;
     }
@@ -235,6 +597,207 @@ impl GameScreen {
     }
   }
 
+  fn find_player(&self) -> Option<usize> {
+    self.entities.iter().position(|entity| entity.base.entity_type == entity::EntityType::Player)
+  }
+
+  // not in the original game yet: gets the player into the closest vehicle, or out of the current one
+  fn toggle_player_vehicle(&mut self, context: &globals::Context) {
+    let player_id = match self.find_player() {
+      Some(player_id) => player_id,
+      None => return
+    };
+
+    if let Some(vehicle_id) = self.player_vehicle.take() {
+      let player_width = self.entities[player_id].get_class(context).width;
+
+      let (side, angle) = {
+        let vehicle = &mut self.entities[vehicle_id];
+
+        // given back to whatever drove it before. traffic drives off again from a standstill,
+        // once any speed left over from the player has slid out, like after being hit
+        vehicle.base.entity_type = self.player_vehicle_type;
+        vehicle.base.speed = 0.;
+        if !vehicle.base.broken {
+          if vehicle.base.entity_type.is_npc() {
+            vehicle.base.set_new_stance(entity::EntityStance::Running);
+          } else {
+            vehicle.base.set_new_stance(entity::EntityStance::Standing);
+          }
+        }
+
+        let distance = vehicle.get_class(context).height + player_width + 2.;
+        (vehicle.base.pos + util::cossin(vehicle.base.angle - util::HALF_PI) * distance, vehicle.base.angle)
+      };
+
+      let player = &mut self.entities[player_id];
+      player.base.hidden = false;
+      player.base.angle = angle;
+      player.base.set_new_stance(entity::EntityStance::Standing);
+      player.teleport(side);
+    } else if let Some(vehicle_id) = vehicle::find_vehicle_to_enter(&self.entities, self.entities[player_id].base.pos) {
+      {
+        let player = &mut self.entities[player_id];
+        player.base.speed = 0.;
+        player.base.hidden = true;
+        player.base.set_new_stance(entity::EntityStance::Riding);
+      }
+
+      self.player_vehicle_type = self.entities[vehicle_id].base.entity_type;
+      self.entities[vehicle_id].base.entity_type = entity::EntityType::PlayerVehicle;
+      self.player_vehicle = Some(vehicle_id);
+    }
+  }
+
+  // not in the original game
+  fn run_command(&mut self, context: &mut globals::Context, line: &str) {
+    let command = match console::parse_command(line) {
+      Ok(command) => command,
+      Err(error) => {
+        if !error.is_empty() {
+          self.console.print(&error[..]);
+        }
+        return;
+      }
+    };
+
+    match command {
+      console::Command::Help => {
+        for line in console::HELP.iter() {
+          self.console.print(line);
+        }
+      },
+      console::Command::Spawn(class) => {
+        if class < 0 || class as usize >= context.data.classes.len() {
+          self.console.print(&format!("No class {}", class)[..]);
+          return;
+        }
+
+        let pos = match self.find_player() {
+          Some(player_id) => self.entities[player_id].base.pos + Vec3f::new2(0., -2. * util::TILESIZE as FScalar),
+          None => Vec3f::from(self.camera.middle())
+        };
+
+        let id = self.entities.len();
+        let mut entity = entity::Entity::new(context, id as EntityId, class);
+        entity.base.hidden = false;
+        entity.after_init(context);
+        entity.teleport(pos);
+
+        self.console.print(&format!("Spawned {} {:?} at {:?}", id, entity.base.entity_type, pos)[..]);
+        self.entities.push(entity);
+      },
+      console::Command::Teleport(pos) => {
+        let player_id = match self.find_player() {
+          Some(player_id) => player_id,
+          None => {
+            self.console.print("No player in this level");
+            return;
+          }
+        };
+
+        self.entities[player_id].teleport(pos);
+        if let Some(vehicle_id) = self.player_vehicle {
+          self.entities[vehicle_id].teleport(pos);
+        }
+      },
+      console::Command::Level(levelid) => {
+        if levelid < 0 || levelid as usize >= context.data.levels.len() {
+          self.console.print(&format!("No level {}", levelid)[..]);
+          return;
+        }
+
+        self.layer1_chunks.clear(context);
+        self.shadow_chunks.clear(context);
+        self.layer2_chunks.clear(context);
+
+        let mut game = GameScreen::new(context, levelid);
+        // straight into the level, without the pause menu
+        game.dialogs.clear();
+        game.control_scheme = self.control_scheme;
+        game.playercontroller = self.control_scheme.create_controller();
+        game.debug_overlay = self.debug_overlay;
+        game.console = std::mem::replace(&mut self.console, console::Console::new());
+
+        *self = game;
+        self.init(context);
+        self.console.print(&format!("Switched to level {}", levelid)[..]);
+      },
+      console::Command::TimeScale(scale) => {
+        context.time_scale = scale;
+      },
+      console::Command::Overlay(None) => {
+        self.debug_overlay.toggle();
+      },
+      console::Command::Overlay(Some(name)) => {
+        if !self.debug_overlay.toggle_part(&name[..]) {
+          self.console.print(&format!("No overlay {}", name)[..]);
+        }
+      },
+      console::Command::Entities => {
+        for (i, entity) in self.entities.iter().enumerate() {
+          self.console.print(&format!("{}: {:?} class {} {:?} at {:?}{}",
+                                      i, entity.base.entity_type, entity.base.class, entity.base.stance,
+                                      Vec3i::from(entity.base.pos),
+                                      if entity.base.hidden { " hidden" } else { "" })[..]);
+        }
+      },
+      console::Command::Entity(id) => {
+        match self.entities.get(id) {
+          Some(entity) => {
+            // too long for the console
+            println!("{:#?}", entity.base);
+            self.console.print(&format!("{}: {:?} health {} speed {} angle {}",
+                                        id, entity.base.entity_type, entity.base.health,
+                                        entity.base.speed, entity.base.angle)[..]);
+          },
+          None => self.console.print(&format!("No entity {}", id)[..])
+        }
+      },
+      console::Command::List(table) => {
+        match console::list_data(context, &table[..]) {
+          Some(lines) => {
+            for line in lines.iter() {
+              self.console.print(line);
+            }
+          },
+          None => self.console.print(&format!("No table {}", table)[..])
+        }
+      }
+    }
+  }
+
+  fn step_explosions(&mut self, context: &globals::Context) {
+    for explosion in std::mem::replace(&mut self.explosions, vec![]) {
+      self.new_alarms.push(person::Alarm {
+        pos: explosion.pos
+      });
+      for entity in self.entities.iter_mut() {
+        if entity.base.hidden || entity.base.id == explosion.source {
+          continue;
+        }
+
+        if let Some((damage, push, impact)) = explosion.get_effect(entity.base.pos) {
+          entity.damage(context, damage);
+
+          let end_pos = entity.base.pos + push;
+          entity.set_pos(end_pos);
+          entity.on_collision(push, impact);
+        }
+      }
+    }
+  }
+
+  // the player rides along, hidden
+  fn step_player_vehicle(&mut self) {
+    if let Some(vehicle_id) = self.player_vehicle {
+      if let Some(player_id) = self.find_player() {
+        let pos = self.entities[vehicle_id].base.pos;
+        self.entities[player_id].teleport(pos);
+      }
+    }
+  }
+
   fn get_spawn_xy(current: IScalar, max: IScalar) -> Option<Vec3i> {
     if current >= max * 8 {
       return None;
@@ -260,15 +823,17 @@ impl GameScreen {
     Some(Vec3::new2(x, y))
   }
 
-  fn find_entity_spawn_point(level: &Level, entity: &mut entity::Entity,
+  fn find_entity_spawn_point(context: &mut globals::Context, view: &GameView, entity: &mut entity::Entity,
                              x: IScalar, y: IScalar, border: IScalar) -> Option<Vec3f> {
+    let level = view.level;
+
     // TODO: do proper checks
     let x = x / level.tilesize.x as IScalar;
     let y = y / level.tilesize.x as IScalar;
     let border = border / level.tilesize.x as IScalar;
 
     let max = border * 8;
-    let mut current = util::pick_int(max);
+    let mut current = util::pick_int(context, max);
     loop {
       let spawn_xy = GameScreen::get_spawn_xy(current, border);
       if let Some(spawn_xy) = spawn_xy {
@@ -279,7 +844,7 @@ impl GameScreen {
           spawn_xy.y < level.tiledata_size.y {
             let pos = Vec3f::from(spawn_xy) * level.tilesize + level.tilesize / 2.;
 
-            if let Some(pos) = entity.spawn(pos) {
+            if let Some(pos) = entity.spawn(context, view, pos) {
               return Some(pos);
             }
           }
@@ -293,8 +858,8 @@ impl GameScreen {
     None
   }
 
-  fn apply_entity_spawn_point(level: &Level, entity: &mut entity::Entity, pos: Vec3i, border: IScalar) -> bool {
-    let pos = GameScreen::find_entity_spawn_point(level, entity, pos.x, pos.y, border);
+  fn apply_entity_spawn_point(context: &mut globals::Context, view: &GameView, entity: &mut entity::Entity, pos: Vec3i, border: IScalar) -> bool {
+    let pos = GameScreen::find_entity_spawn_point(context, view, entity, pos.x, pos.y, border);
     return pos.is_some();
     /*if let Some(pos) = pos {
       entity.base.pos = pos;
@@ -305,18 +870,19 @@ impl GameScreen {
     }*/
   }
 
-  fn step_entity_despawn(&mut self) {
+  fn step_entity_despawn(&mut self, context: &mut globals::Context) {
     loop {
       let mut entity_found = true;
 
       let entities_len = self.entities.len(); // because of borrow
-      let entity = &mut self.entities[self.entity_spawn_counter % entities_len];
+      let camera = self.camera.clone();
+      let (entity, view) = self.get_entity_view(self.entity_spawn_counter % entities_len);
 
       // TODO: check for flag 0x10000 == 0
       if entity.base.entity_type.is_npc() {
-        if self.camera.out_of_screen(entity.base.pos.into()) {
+        if camera.out_of_screen(entity.base.pos.into()) {
           if entity.despawn() {
-            GameScreen::apply_entity_spawn_point(&self.level, entity, self.camera.middle(), self.camera.size.min2());
+            GameScreen::apply_entity_spawn_point(context, &view, entity, camera.middle(), camera.size.min2());
           }
         }
       } else if entity.base.entity_type != entity::EntityType::Player {
@@ -330,115 +896,182 @@ impl GameScreen {
     }
   }
 
-  fn create_entity_ids(&mut self) {
-    self.entity_ids = vec![];
+  // not in the original game
+  // fills the level with copies of its entities, then times step_collision against checking every pair
+  pub fn benchmark_collision(&mut self, context: &mut globals::Context, count: usize) {
+    const ITERATIONS: u32 = 100;
 
-    for i in 0..self.entities.len() {
-      self.entity_ids.push(i as EntityId);
+    let classes: Vec<ClassId> = self.entities.iter()
+      .filter(|entity| entity.base.entity_type.is_person() || entity.base.entity_type.is_vehicle())
+      .map(|entity| entity.base.class)
+      .collect();
+
+    if classes.len() == 0 || self.entities.len() == 0 {
+      println!("No entities to copy in level {}", self.levelid);
+      return;
     }
 
-    GameScreen::update_entity_ids(&mut self.entity_ids, &self.entities);
-  }
+    // about one entity per 2x2 tiles, around the player
+    let area = ((count as FScalar).sqrt() * 2. * util::TILESIZE as FScalar).ceil();
+    let start = self.entities[0].base.pos - area / 2.;
 
-  fn update_entity_ids(entity_ids: &mut Vec<EntityId>, entities: &Vec<entity::Entity>) {
-    entity_ids.sort_by(|a, b| {
-      let entity_a = &entities[*a as usize];
-      let entity_b = &entities[*b as usize];
+    for i in 0..count {
+      let id = self.entities.len() as EntityId;
+      let mut entity = entity::Entity::new(context, id, classes[i % classes.len()]);
+      let pos = start + Vec3f::new2(util::pick_float(context, area), util::pick_float(context, area));
 
-      entity_a.base.sort_order.cmp(&entity_b.base.sort_order)
-    });
-  }
-}
+      entity.base.hidden = false;
+      entity.after_init(context);
+      entity.teleport(pos);
 
-impl Screen for GameScreen {
-  fn init(&mut self) {
-    image::load_image(3, 0);
-    image::load_image(0, 0);
-    image::load_image(6, 0); // human without palette, temporarily needed
-    //image::load_image(8, 0);
+      self.entities.push(entity);
+    }
 
-    // player images
-    image::load_image(8, 11); // female
-    image::load_image(6, 11); // male
+    let start_positions: Vec<Vec3f> = self.entities.iter().map(|entity| entity.base.pos).collect();
+    let physical: Vec<usize> = (0..self.entities.len()).filter(|id| self.entities[*id].is_physical()).collect();
 
-    image::load_image(6, 12);
-    image::load_image(6, 13);
-    image::load_image(8, 12);
+    // the grid has to find every pair that actually touches
+    self.update_collision_grid();
+    let grid_pairs = self.collision_grid.get_pairs();
+    let mut touching = 0;
+    let mut missed = 0;
+    for pair in collision::get_pairs_brute_force(&physical) {
+      if GameScreen::get_entity_response(&self.entities[pair.0], &self.entities[pair.1]).is_some() {
+        touching += 1;
 
-    //image::load_image(5, 0);
-    //image::load_image(7, 0);
-    image::load_image(4, 0); // policecar
+        if grid_pairs.binary_search(&pair).is_err() {
+          missed += 1;
+        }
+      }
+    }
 
-    image::load_image(12, 0); // policeman
-    image::load_image(11, 0);
+    let to_millis = |duration: std::time::Duration| {
+      (duration.as_secs() as f64 * 1000. + duration.subsec_nanos() as f64 / 1000000.) / ITERATIONS as f64
+    };
 
-    //image::load_image(13, 0);
-    image::load_image(16, 0);
-    //image::load_image(18, 0);
-    image::load_image(25, 0);
-    image::load_image(27, 0);
-    image::load_image(31, 0);
-    image::load_image(29, 0);
-    image::load_image(33, 0);
+    let mut grid_time = std::time::Duration::default();
+    let mut brute_force_time = std::time::Duration::default();
 
-    for i in 1..4 {
-      image::load_image(18, i);
-    }
-    for i in 1..4 {
-      image::load_image(5, i);
-    }
-    for i in 1..4 {
-      image::load_image(7, i);
+    for _i in 0..ITERATIONS {
+      for (entity, pos) in self.entities.iter_mut().zip(start_positions.iter()) {
+        entity.teleport(*pos);
+      }
+
+      let start_time = std::time::Instant::now();
+      self.step_collision(0);
+      grid_time += start_time.elapsed();
+
+      for (entity, pos) in self.entities.iter_mut().zip(start_positions.iter()) {
+        entity.teleport(*pos);
+      }
+
+      let start_time = std::time::Instant::now();
+      let pairs = collision::get_pairs_brute_force(&physical);
+      self.step_collision_pairs(&pairs, 0);
+      brute_force_time += start_time.elapsed();
     }
-    for i in 1..4 {
-      image::load_image(13, i);
+
+    println!("{} entities, {} physical, {} touching pairs", self.entities.len(), physical.len(), touching);
+    println!("  grid:      {:.3}ms per step ({} pairs checked)", to_millis(grid_time), grid_pairs.len());
+    println!("  all pairs: {:.3}ms per step", to_millis(brute_force_time));
+
+    if missed > 0 {
+      println!("  grid missed {} touching pairs!", missed);
     }
+  }
+}
+
+impl Screen for GameScreen {
+  fn init(&mut self, context: &mut globals::Context) {
+    // these are drawn with their first palette by default
+    image::copy_image_palette(context, 18, 1, 0);
+    image::copy_image_palette(context, 5, 1, 0);
+    image::copy_image_palette(context, 7, 1, 0);
+    image::copy_image_palette(context, 13, 1, 0);
 
-    image::copy_image_palette(18, 1, 0);
-    image::copy_image_palette(5, 1, 0);
-    image::copy_image_palette(7, 1, 0);
-    image::copy_image_palette(13, 1, 0);
+    level::load_images(context, self.levelid);
 
-    level::load_images(self.levelid);
+    self.layer1_chunks.clear(context);
+    self.shadow_chunks.clear(context);
+    self.layer2_chunks.clear(context);
 
-    self.entities = level::load_entities(&self.level);
-    self.create_entity_ids();
+    self.entities = level::load_entities(context, &self.level);
 
     for entity in self.entities.iter_mut() {
-      entity.after_init();
+      entity.after_init(context);
+
+      // not in the original game, vehicles don't follow routes yet
+      if context.route_events && entity.base.entity_type.is_person() {
+        entity.base.follow_level_route(&self.level, ROUTE_SPEED);
+      }
     }
   }
 
-  fn step(&mut self, delta: Time) {
-    self.process_input();
+  fn step(&mut self, context: &mut globals::Context, delta: Time) {
+    self.process_input(context);
 
-    self.vehicle_state.step();
+    self.vehicle_state.step(context.time);
 
-    self.step_entity_despawn();
+    self.step_entity_despawn(context);
 
-    for entity in self.entities.iter_mut() {
-      /*if (entity.base.route.route.is_none() &&
-          entity.base.route.routeid.is_some()) {
-        entity.base.route.set_route_to_routeid();
-        entity.base.set_new_stance(entity::EntityStance::Running);
-        entity.base.speed = 20.;
-      }*/
+    for i in 0..self.entities.len() {
+      let (entity, mut view) = self.get_entity_view(i);
 
-      entity.step(delta);
+      entity.step(context, &mut view, delta);
     }
 
     self.step_collision(delta);
+    self.step_explosions(context);
+    self.step_player_vehicle();
+    self.particles.step(delta);
+
+    self.alarms = std::mem::replace(&mut self.new_alarms, vec![]);
+    self.route_triggers = std::mem::replace(&mut self.new_route_triggers, vec![]);
   }
 
-  fn set_size(&mut self, size: Vec3i) {
+  fn input(&mut self, context: &mut globals::Context, event: Event) -> bool {
+    self.console.watch_key(&context.input.bindings, event);
+
+    if self.console.open {
+      match event {
+        Event::Key { .. } | Event::TextInput(_) => {
+          if let Some(line) = self.console.input(&context.input.bindings, event) {
+            self.run_command(context, &line[..]);
+          }
+
+          return true;
+        },
+        _ => {}
+      }
+    }
+
+    let mut consumed = false;
+
+    if let Some(dialog) = self.dialogs.last_mut() {
+      consumed = dialog.input(context, event);
+    }
+
+    if self.dialogs.len() > 0 && self.dialogs[self.dialogs.len() - 1].is_closed() {
+      self.dialogs.pop();
+    }
+
+    consumed
+  }
+
+  fn set_size(&mut self, context: &mut globals::Context, size: Vec3i) {
     for dialog in self.dialogs.iter_mut() {
-      dialog.set_boundaries(dialog::SizeBoundary::Both(size));
+      dialog.set_boundaries(context, dialog::SizeBoundary::Both(size));
     }
   }
 
-  fn draw(&mut self) {
-    let context = globals::get_context();
+  fn render_targets_reset(&mut self, context: &mut globals::Context) {
+    // baked again as they come into view
+    self.layer1_chunks.clear(context);
+    self.shadow_chunks.clear(context);
+    self.layer2_chunks.clear(context);
+  }
 
+  fn draw(&mut self, context: &mut globals::Context) {
     context.platform.set_color(Color { r: 0, g: 0, b: 0, a: 255 });
     context.platform.clear();
     context.platform.reset();
@@ -447,38 +1080,77 @@ impl Screen for GameScreen {
     context.platform.scale(self.scale);
     //context.platform.draw_region(&image, 0, 0, x, x, 0, None, 50, 10);
 
-    GameScreen::update_entity_ids(&mut self.entity_ids, &self.entities);
+    let camera = self.camera.clone();
 
-    level::draw_level_layer(&self.level.layer1);
-    level::draw_shadows(&self.level);
-    level::draw_objects(&self.level, &self.entities, &self.entity_ids);
-    level::draw_level_layer(&self.level.layer2);
+    let mut queue = render::RenderQueue::new(context);
+    queue.push(render::Layer::Ground, render::Drawable::Chunks(&mut self.layer1_chunks, level::ChunkSource::Layer(&self.level.layer1)));
+    queue.push(render::Layer::Ground, render::Drawable::Skidmarks(&self.skidmarks));
+    queue.push(render::Layer::Shadows, render::Drawable::Chunks(&mut self.shadow_chunks, level::ChunkSource::Shadows(&self.level)));
 
-    sprite::draw_sprite(1117, Vec3i::new2(50, 50), 0);
+    level::queue_objects(context, &mut queue, &self.level);
+    for entity in self.entities.iter() {
+      queue.push_entity(context, entity);
+    }
+
+    queue.push(render::Layer::Overhead, render::Drawable::Chunks(&mut self.layer2_chunks, level::ChunkSource::Layer(&self.level.layer2)));
+    queue.push(render::Layer::Effects, render::Drawable::Particles(&self.particles));
+
+    queue.push(render::Layer::Effects, render::Drawable::Sprite { sprite: 1117, pos: Vec3i::new2(50, 50), flip: 0 });
 
     // draw camera
-    context.platform.set_color(Color { r: 255, g: 0, b: 0, a: 255 });
-    context.platform.fill_rect(self.camera.pos.x - 2,
-                               self.camera.pos.y - 2,
-                               self.camera.size.x + 2,
-                               4);
-    context.platform.fill_rect(self.camera.pos.x - 2,
-                               self.camera.pos.y + self.camera.size.y - 2,
-                               self.camera.size.x + 2,
-                               4);
-    context.platform.fill_rect(self.camera.pos.x - 2,
-                               self.camera.pos.y - 2,
-                               4,
-                               self.camera.size.y + 2);
-    context.platform.fill_rect(self.camera.pos.x + self.camera.size.x - 2,
-                               self.camera.pos.y - 2,
-                               4,
-                               self.camera.size.y + 2);
+    queue.push(render::Layer::Effects, render::Drawable::Custom(Box::new(move |context: &mut globals::Context| {
+      context.platform.set_color(Color { r: 255, g: 0, b: 0, a: 255 });
+      context.platform.fill_rect(camera.pos.x - 2,
+                                 camera.pos.y - 2,
+                                 camera.size.x + 2,
+                                 4);
+      context.platform.fill_rect(camera.pos.x - 2,
+                                 camera.pos.y + camera.size.y - 2,
+                                 camera.size.x + 2,
+                                 4);
+      context.platform.fill_rect(camera.pos.x - 2,
+                                 camera.pos.y - 2,
+                                 4,
+                                 camera.size.y + 2);
+      context.platform.fill_rect(camera.pos.x + camera.size.x - 2,
+                                 camera.pos.y - 2,
+                                 4,
+                                 camera.size.y + 2);
+    })));
+
+    debug::queue_overlay(&mut queue, self.debug_overlay, &self.level, &self.entities[..], self.vehicle_state.get_trafficlight());
+
+    if self.console.open {
+      queue.push(render::Layer::Console, render::Drawable::Console(&self.console));
+    }
 
+    if let Some(dialog) = self.dialogs.last() {
+      queue.push(render::Layer::Dialogs, render::Drawable::Dialog(dialog));
+    }
+
+    queue.draw(context);
     context.platform.reset();
+  }
+}
 
-    if self.dialogs.len() > 0 {
-      self.dialogs[self.dialogs.len() - 1].draw(Vec3i::default());
-    }
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn make_object(weight: IScalar) -> collision::PhysicalObject {
+    collision::PhysicalObject::new_from_info(collision::ShapeInfo {
+      shape: collision::Shape::Circle(8),
+      weight
+    })
+  }
+
+  #[test]
+  fn lighter_entity_moves_further() {
+    let (light, heavy) = GameScreen::get_weight_split(&make_object(10), &make_object(1000));
+    assert!(light > heavy, "{} <= {}", light, heavy);
+    assert!((light + heavy - 1.).abs() < 1e-9);
+
+    let (heavy, light) = GameScreen::get_weight_split(&make_object(1000), &make_object(10));
+    assert!(light > heavy, "{} <= {}", light, heavy);
   }
 }
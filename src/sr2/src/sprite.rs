@@ -1,5 +1,6 @@
 use opensrme_common::*;
 use super::*;
+use std::collections::HashMap;
 
 fn read_drawcommand(info: &Vec<i16>, pos: usize) -> (usize, DrawCommand) {
   return match info[pos] & 0xff {
@@ -80,7 +81,8 @@ pub fn create_sprite(info: Vec<i16>, aabb: Vec<i16>) -> Sprite {
 
   return Sprite {
     aabb: aabb,
-    draw: drawcommands
+    draw: drawcommands,
+    compiled: Default::default()
   };
 }
 
@@ -104,135 +106,247 @@ pub fn calc_aabb(sprite: &Sprite, pos: Vec3i, flip: Flip) -> [i16; 4] {
   [x, y, x + width, y + height]
 }
 
-pub fn draw_sprite_palette(spriteid: SpriteId, pos: Vec3i, flip: Flip, palette_map: &Vec<(ImageId, PaletteId)>) {
-  let context = globals::get_context();
+impl FrameCondition {
+  fn matches(&self, time: Time) -> bool {
+    (self.frame as u64) == (time / (self.total_time as u64)) % (self.frames as u64)
+  }
+}
 
-  let sprite = &context.data.sprites[spriteid as usize];
-  let aabb = calc_aabb(&sprite, pos, flip);
+impl DrawOp {
+  pub fn is_visible(&self, time: Time) -> bool {
+    self.conditions.iter().all(|condition| condition.matches(time))
+  }
+
+  fn offset(&self, offset: Vec3i, condition: Option<FrameCondition>) -> DrawOp {
+    let kind = match self.kind {
+      DrawOpKind::Image { image_id, src, size, dest, flip } => DrawOpKind::Image {
+        image_id, src, size, flip,
+        dest: dest + offset
+      },
+      DrawOpKind::SetColor(color) => DrawOpKind::SetColor(color),
+      DrawOpKind::Line { from, to } => DrawOpKind::Line {
+        from: from + offset,
+        to: to + offset
+      },
+      DrawOpKind::Shape { shape, pos, size } => DrawOpKind::Shape {
+        shape, size,
+        pos: pos + offset
+      }
+    };
 
-  let mut flip = flip;
+    let mut conditions = vec![];
+    conditions.extend(condition);
+    conditions.extend(self.conditions.iter().cloned());
 
-  let mut startx = 0;
-  let mut starty = 0;
-  let mut hidden = false;
+    DrawOp {
+      kind,
+      conditions
+    }
+  }
+}
 
-  //println!("{} {:?}\n  {:?}", spriteid, sprite, aabb);
-  for command in sprite.draw.iter() {
-    match *command {
-      DrawCommand::Image {
-        image_id,
-        start_x,
-        start_y
-      } => {
-        //println!("{:?}", context.images[image_id as usize]);
+struct SpriteCompiler<'a> {
+  sprites: &'a [Sprite],
+  compiled: HashMap<(SpriteId, Flip), Vec<DrawOp>>,
+  // sprites being compiled, to find ones that end up drawing themselves
+  stack: Vec<SpriteId>
+}
+
+impl<'a> SpriteCompiler<'a> {
+  fn compile(&mut self, spriteid: SpriteId, flip: Flip) -> Vec<DrawOp> {
+    if let Some(ops) = self.compiled.get(&(spriteid, flip)) {
+      return ops.clone();
+    }
+
+    let sprite = &self.sprites[spriteid as usize];
+    let aabb = calc_aabb(sprite, Vec3i::default(), flip);
+
+    self.stack.push(spriteid);
+
+    let initial_flip = flip;
+    let mut ops = vec![];
+    let mut flip = flip;
+    let mut start = Vec3i::default();
+    let mut condition = None;
+
+    for command in sprite.draw.iter() {
+      match *command {
+        DrawCommand::Image { image_id, start_x, start_y } => {
+          ops.push(DrawOp {
+            kind: DrawOpKind::Image {
+              image_id: image_id as ImageId,
+              src: Vec3i::new2(start_x as IScalar, start_y as IScalar),
+              size: Vec3i::new2((aabb[2] - aabb[0]) as IScalar, (aabb[3] - aabb[1]) as IScalar),
+              dest: Vec3i::new2(aabb[0] as IScalar, aabb[1] as IScalar),
+              flip
+            },
+            conditions: vec![]
+          });
+        },
+        DrawCommand::HFlip => {
+          flip ^= FLIP_H;
+        },
+        DrawCommand::VFlip => {
+          flip ^= FLIP_V;
+        },
+        DrawCommand::SetOffset { x, y } => {
+          start.x = if (flip & FLIP_H) != 0 { -x } else { x } as IScalar;
+          start.y = if (flip & FLIP_V) != 0 { -y } else { y } as IScalar;
+        },
+        DrawCommand::DrawSprite(child) => {
+          if child < 0 || child as usize >= self.sprites.len() {
+            println!("Sprite {} draws missing sprite {}", spriteid, child);
+            continue;
+          }
+
+          if self.stack.contains(&child) {
+            println!("Sprite {} draws itself through {:?}", child, self.stack);
+            continue;
+          }
+
+          for op in self.compile(child, flip).iter() {
+            ops.push(op.offset(start, condition));
+          }
+        },
+        DrawCommand::SetFrame { frame, total_time, frames } => {
+          condition = Some(FrameCondition {
+            frame,
+            total_time,
+            frames
+          });
+        },
+        DrawCommand::SetColor(color) => {
+          ops.push(DrawOp {
+            kind: DrawOpKind::SetColor(color),
+            conditions: vec![]
+          });
+        },
+        DrawCommand::DrawShape { shape, x, y } => {
+          let flip_h = (flip & FLIP_H) != 0;
+          let flip_v = (flip & FLIP_V) != 0;
+
+          // x and y are the end of the line, relative to the offset
+          let kind = if shape == DrawShape::Line {
+            let end = Vec3i::new2((if flip_h { -x } else { x }) as IScalar,
+                                  (if flip_v { -y } else { y }) as IScalar);
+            DrawOpKind::Line {
+              from: start,
+              to: start + end
+            }
+          } else {
+            // otherwise they're the size. filled shapes cover x * y pixels, outlines one more,
+            // flipping mirrors pixel n to -n like the images
+            let covered = match shape {
+              DrawShape::FillRect | DrawShape::FillArc => Vec3i::new2(x as IScalar, y as IScalar),
+              _ => Vec3i::new2(x as IScalar + 1, y as IScalar + 1)
+            };
+
+            DrawOpKind::Shape {
+              shape,
+              pos: Vec3i::new2(if flip_h { start.x - covered.x + 1 } else { start.x },
+                               if flip_v { start.y - covered.y + 1 } else { start.y }),
+              size: Vec3i::new2(x as IScalar, y as IScalar)
+            }
+          };
+
+          ops.push(DrawOp {
+            kind,
+            conditions: vec![]
+          });
+        },
+        DrawCommand::Invalid => {}
+      }
+    }
+
+    self.stack.pop();
+    self.compiled.insert((spriteid, initial_flip), ops.clone());
+    ops
+  }
+}
+
+pub fn compile_sprites(sprites: &mut Vec<Sprite>) {
+  let mut all_compiled = vec![];
+
+  {
+    let mut compiler = SpriteCompiler {
+      sprites: &sprites[..],
+      compiled: HashMap::new(),
+      stack: vec![]
+    };
+
+    for spriteid in 0..sprites.len() {
+      let mut compiled: [Vec<DrawOp>; 4] = Default::default();
+      for flip in 0..4 {
+        compiled[flip] = compiler.compile(spriteid as SpriteId, flip as Flip);
+      }
+
+      all_compiled.push(compiled);
+    }
+  }
+
+  for (sprite, compiled) in sprites.iter_mut().zip(all_compiled.into_iter()) {
+    sprite.compiled = compiled;
+  }
+}
+
+pub fn draw_sprite_palette(context: &mut globals::Context, spriteid: SpriteId, pos: Vec3i, flip: Flip, palette_map: &Vec<(ImageId, PaletteId)>) {
+  let time = context.time;
+  // copied out, drawing images needs the context again
+  let ops: Vec<DrawOpKind> = context.data.sprites[spriteid as usize].compiled[(flip & 3) as usize].iter()
+    .filter(|op| op.is_visible(time))
+    .map(|op| op.kind)
+    .collect();
+
+  for op in ops {
+    match op {
+      DrawOpKind::Image { image_id, src, size, dest, flip } => {
         let mut palette: PaletteId = 0;
         for item in palette_map.iter() {
-          if item.0 == image_id as ImageId {
+          if item.0 == image_id {
             palette = item.1;
           }
         }
 
-        let platform_id = context.palette_images[palette as usize][image_id as usize];
+        let platform_id = image::load_image(context, image_id, palette);
         if platform_id == 0 {
-          //println!("Attempting to draw non-loaded image: {{ I: {}, P: {} }}", image_id, palette);
           continue;
         }
 
-        context.platform.draw_region(
-          platform_id,
-          start_x as IScalar,
-          start_y as IScalar,
-          (aabb[2] - aabb[0]) as IScalar,
-          (aabb[3] - aabb[1]) as IScalar,
-          flip,
-          None,
-          aabb[0] as IScalar,
-          aabb[1] as IScalar
-        );
+        context.platform.draw_region(platform_id, src.x, src.y, size.x, size.y, flip, None, pos.x + dest.x, pos.y + dest.y);
       },
-      DrawCommand::HFlip => {
-        flip ^= FLIP_H;
+      DrawOpKind::SetColor(color) => {
+        context.platform.set_color(color);
       },
-      DrawCommand::VFlip => {
-        flip ^= FLIP_V;
+      DrawOpKind::Line { from, to } => {
+        context.platform.draw_line(pos.x + from.x, pos.y + from.y, pos.x + to.x, pos.y + to.y);
       },
-      DrawCommand::SetOffset { x, y } => {
-        if (flip & FLIP_H) != 0 {
-          startx = -x;
-        } else {
-          startx = x;
-        }
+      DrawOpKind::Shape { shape, pos: shape_pos, size } => {
+        let x = pos.x + shape_pos.x;
+        let y = pos.y + shape_pos.y;
 
-        if (flip & FLIP_V) != 0 {
-          starty = -y;
-        } else {
-          starty = y;
-        }
-      },
-      DrawCommand::DrawSprite(new_spriteid) => {
-        if !hidden {
-          //println!("{} {}", new_spriteid, spriteid);
-          if new_spriteid as SpriteId == spriteid as SpriteId {
-            println!("Same sprite id: {}", spriteid);
-          } else {
-            draw_sprite_palette(new_spriteid as SpriteId, Vec3i::new2(pos.x + startx as i32, pos.y + starty as i32), flip, palette_map);
-          }
-        }
-      },
-      DrawCommand::SetFrame {
-        frame,
-        total_time,
-        frames
-      } => {
-        if (frame as u64) == (context.time / (total_time as u64)) % (frames as u64) {
-          hidden = false;
-        } else {
-          hidden = true;
-        }
-      },
-      DrawCommand::SetColor(color) => {
-        context.platform.set_color(color)
-      },
-      DrawCommand::DrawShape {
-        shape, x, y
-      } => {
-        let mut draw_x = pos.x + startx as IScalar;
-        let mut draw_y = pos.y + starty as IScalar;
-
-        if (flip & FLIP_H) != 0 {
-          draw_x -= x as IScalar;
-        }
-
-        if (flip & FLIP_V) != 0 {
-          draw_y -= y as IScalar;
-        }
         match shape {
-          DrawShape::FillRect => {
-            context.platform.fill_rect(draw_x, draw_y, x.into(), y.into());
-          },
-          _ => {
-          }
+          DrawShape::FillRect => context.platform.fill_rect(x, y, size.x, size.y),
+          DrawShape::DrawRect => context.platform.draw_rect(x, y, size.x, size.y),
+          DrawShape::FillArc => context.platform.fill_arc(x, y, size.x, size.y, 0, 360),
+          DrawShape::DrawArc => context.platform.draw_arc(x, y, size.x, size.y, 0, 360),
+          DrawShape::Line => {}
         }
       }
-      _ => {
-      }
     }
   }
 }
 
-pub fn draw_sprite(spriteid: SpriteId, pos: Vec3i, flip: Flip) {
-  draw_sprite_palette(spriteid, pos, flip, &vec![])
+pub fn draw_sprite(context: &mut globals::Context, spriteid: SpriteId, pos: Vec3i, flip: Flip) {
+  draw_sprite_palette(context, spriteid, pos, flip, &vec![])
 }
 
-pub fn get_image_from_sprite(spriteid: SpriteId) -> Option<ImageId> {
-  let context = globals::get_context();
-
+pub fn get_image_from_sprite(context: &globals::Context, spriteid: SpriteId) -> Option<ImageId> {
   let sprite = &context.data.sprites[spriteid as usize];
 
   for command in sprite.draw.iter() {
     match *command {
       DrawCommand::Image { image_id, .. } => return Some(image_id.into()),
-      DrawCommand::DrawSprite(new_spriteid) => return get_image_from_sprite(new_spriteid),
+      DrawCommand::DrawSprite(new_spriteid) => return get_image_from_sprite(context, new_spriteid),
       // FIXME: this doesn't match the original algorithm
       _ => {}
     }
@@ -1,29 +1,26 @@
 use super::*;
 
-pub fn load_font(fontid: FontId) -> PlatformId {
-  let context = globals::get_context();
-  let font = &context.data.fonts[fontid as usize];
-  let path = font.name.clone() + ".png";
+pub fn load_font(context: &mut globals::Context, fontid: FontId) -> PlatformId {
+  let font_palette = context.data.fonts[fontid as usize].palette;
+  let path = context.data.fonts[fontid as usize].name.clone() + ".png";
 
-  let imageid = if font.palette > 0 {
-    let palette = &context.data.palettes[font.palette as usize];
-    image::new_with_path_palette(&path[..], palette)
+  let imageid = if font_palette > 0 {
+    let palette = context.data.palettes[font_palette as usize].clone();
+    image::new_with_path_palette(context, &path[..], &palette)
   } else {
-    image::new(&path[..])
+    image::new(context, &path[..])
   };
 
   context.font_images[fontid as usize] = imageid;
 
   let font_size = context.platform.get_image_size(imageid).unwrap();
 
-  let context = globals::get_context();
   context.data.fonts[fontid as usize].height = font_size.y as i16;
 
   imageid
 }
 
-pub fn text_size(fontid: FontId, text: &str) -> Vec3i {
-  let context = globals::get_context();
+pub fn text_size(context: &globals::Context, fontid: FontId, text: &str) -> Vec3i {
   let font = &context.data.fonts[fontid as usize];
   let widths = &font.widths[0];
   let chars: Vec<char> = text.chars().collect();
@@ -40,8 +37,7 @@ pub fn text_size(fontid: FontId, text: &str) -> Vec3i {
   size
 }
 
-pub fn word_wrap(fontid: FontId, text: &str, limit: IScalar) -> Vec<String> {
-  let context = globals::get_context();
+pub fn word_wrap(context: &globals::Context, fontid: FontId, text: &str, limit: IScalar) -> Vec<String> {
   let font = &context.data.fonts[fontid as usize];
   let widths = &font.widths[0];
 
@@ -101,8 +97,7 @@ pub fn word_wrap(fontid: FontId, text: &str, limit: IScalar) -> Vec<String> {
   result
 }
 
-pub fn draw_text(fontid: FontId, text: &str, pos: Vec3i) -> Vec3i {
-  let context = globals::get_context();
+pub fn draw_text(context: &mut globals::Context, fontid: FontId, text: &str, pos: Vec3i) -> Vec3i {
   let font = &context.data.fonts[fontid as usize];
   let widths = &font.widths[0];
   let offsets = &font.offsets[0];
@@ -95,10 +95,49 @@ pub enum DrawCommand {
   },
 }
 
+// the draw commands flattened, with child sprites, offsets and flips resolved
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DrawOpKind {
+  Image {
+    image_id: ImageId,
+    src: Vec3i,
+    size: Vec3i,
+    dest: Vec3i,
+    flip: Flip
+  },
+  SetColor(Color),
+  Line {
+    from: Vec3i,
+    to: Vec3i
+  },
+  Shape {
+    shape: DrawShape,
+    pos: Vec3i,
+    size: Vec3i
+  }
+}
+
+// SetFrame, drawn when (time / total_time) % frames == frame
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FrameCondition {
+  pub frame: i16,
+  pub total_time: i16,
+  pub frames: i16
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrawOp {
+  pub kind: DrawOpKind,
+  // from the parents drawing this as a child sprite, all of them have to match
+  pub conditions: Vec<FrameCondition>
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Sprite {
   pub aabb: Vec<i16>,
-  pub draw: Vec<DrawCommand>
+  pub draw: Vec<DrawCommand>,
+  // indexed by flip
+  pub compiled: [Vec<DrawOp>; 4]
 }
 
 
@@ -1,4 +1,3 @@
-use rand::Rng;
 use opensrme_common::*;
 use super::*;
 use std::ops::*;
@@ -36,22 +35,22 @@ pub mod angles {
 }
 
 
-pub fn pick_int(n: IScalar) -> IScalar {
-  if n <= 0 {
-    return 0;
-  }
+// gameplay stream, see rng::GameRng
+pub fn pick_int(context: &mut globals::Context, n: IScalar) -> IScalar {
+  context.rng.gameplay.pick_int(n)
+}
 
-  let mut rng = rand::thread_rng();
-  rng.gen_range(0, n)
+pub fn pick_float(context: &mut globals::Context, n: FScalar) -> FScalar {
+  context.rng.gameplay.pick_float(n)
 }
 
-pub fn pick_float(n: FScalar) -> FScalar {
-  if n <= 0. {
-    return 0.;
-  }
+// cosmetic stream, doesn't affect the simulation
+pub fn pick_cosmetic_int(context: &mut globals::Context, n: IScalar) -> IScalar {
+  context.rng.cosmetic.pick_int(n)
+}
 
-  let mut rng = rand::thread_rng();
-  rng.gen_range(0., n)
+pub fn pick_cosmetic_float(context: &mut globals::Context, n: FScalar) -> FScalar {
+  context.rng.cosmetic.pick_float(n)
 }
 
 pub fn normalize_angle(angle: Angle) -> Angle {
@@ -84,6 +83,15 @@ pub fn normalized_angle_diff(angle1: Angle, angle2: Angle) -> Angle {
   normalize_angle_pi(angle1 - angle2)
 }
 
+// shortest way from angle1 to angle2, t = 0..1
+pub fn interpolate_angle(angle1: Angle, angle2: Angle, t: FScalar) -> Angle {
+  angle1 + normalized_angle_diff(angle2, angle1) * t
+}
+
+pub fn vec_dot(vec1: Vec3f, vec2: Vec3f) -> FScalar {
+  vec1.x * vec2.x + vec1.y * vec2.y
+}
+
 pub fn vec_angle(vector: Vec3f) -> Angle {
   vector.y.atan2(vector.x)
 }
@@ -144,11 +152,6 @@ pub fn get_angle_in_clip(angle: Angle, length: usize) -> usize {
   (((normalize_angle(angle) / TWO_PI) * length as Angle).round() as usize) % length
 }
 
-pub fn get_frame_in_clip(time: Time, total_length: usize, length: usize) -> usize {
-  //(time as usize / total_length) % length
-  (time as usize * length) / total_length % length
-}
-
 pub fn cossin(angle: Angle) -> Vec3f {
   Vec3f::new2(
     angle.cos(),
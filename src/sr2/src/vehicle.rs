@@ -1,6 +1,7 @@
 use super::*;
 use util::angles::*;
 use entity::*;
+use std::collections::VecDeque;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum TrafficLight {
@@ -42,10 +43,12 @@ impl VehicleState {
     }
   }
 
-  pub fn step(&mut self) {
-    let context = globals::get_context();
+  pub fn get_trafficlight(&self) -> TrafficLight {
+    self.trafficlight
+  }
 
-    let state_number = (context.time / 3000) % 4;
+  pub fn step(&mut self, time: Time) {
+    let state_number = (time / 3000) % 4;
     self.trafficlight = match state_number {
       0 => TrafficLight::HGreen,
       1 => TrafficLight::HYellow,
@@ -57,7 +60,7 @@ impl VehicleState {
   }
 }
 
-fn is_road(tiledata: LevelTileData) -> bool {
+pub fn is_road(tiledata: LevelTileData) -> bool {
   tiledata >= 10 && tiledata <= 29
 }
 
@@ -69,10 +72,17 @@ fn is_one_way_road(tiledata: LevelTileData) -> bool {
   tiledata >= 10 && tiledata <= 17
 }
 
-fn is_intersection(tiledata: LevelTileData) -> bool {
+pub fn is_intersection(tiledata: LevelTileData) -> bool {
   tiledata >= 18 && tiledata <= 29
 }
 
+// pedestrians walk alongside the traffic that has green, across the road where it's stopped
+pub fn can_cross_road(trafficlight: TrafficLight, angle: Angle) -> bool {
+  let horizontal = angle.cos().abs() > angle.sin().abs();
+
+  trafficlight.is_green() && (trafficlight.index() == 0) == horizontal
+}
+
 const ROAD_DIRECTION_TABLE: [[Angle; 2]; 20] = [
   // 10
   [ANGLE_E, ANGLE_E],
@@ -116,7 +126,7 @@ const ROAD_DIRECTION_TABLE: [[Angle; 2]; 20] = [
   [ANGLE_NW, ANGLE_N]
 ];
 
-fn get_road_direction(tiledata: LevelTileData, trafficlight: TrafficLight) -> Option<Angle> {
+pub fn get_road_direction(tiledata: LevelTileData, trafficlight: TrafficLight) -> Option<Angle> {
   if tiledata < 10 || (tiledata as usize) - 10 >= ROAD_DIRECTION_TABLE.len() {
     None
   } else {
@@ -124,6 +134,17 @@ fn get_road_direction(tiledata: LevelTileData, trafficlight: TrafficLight) -> Op
   }
 }
 
+// not in the original game, used to plan paths that follow the traffic
+pub fn road_allows_direction(tiledata: LevelTileData, angle: Angle) -> bool {
+  if !is_road(tiledata) {
+    return false;
+  }
+
+  ROAD_DIRECTION_TABLE[(tiledata - 10) as usize].iter().any(|direction| {
+    util::normalized_angle_diff(*direction, angle).abs() <= util::QUARTER_PI + 0.01
+  })
+}
+
 //                            e      w      s      n      se     sw     ne     nw
 const E_RULE: [bool; 8] = [ true,  false, false, false, true,  false, true,  false ];
 const W_RULE: [bool; 8] = [ false, true,  false, false, false, true,  false, true  ];
@@ -158,13 +179,12 @@ const ROAD_RULES: [[[bool; 8]; 2]; 12] = [
 ];
 
 // TODO: split into multiple functions like in original game
-fn can_move_on_road(entity: &EntityBase, angle: Angle, last_tiledata: LevelTileData, trafficlight: TrafficLight) -> (bool, i8) {
-  let amount = 3 * (entity.get_class().width as IScalar) >> 1;
+fn can_move_on_road(context: &globals::Context, view: &screen::GameView, entity: &EntityBase, angle: Angle, last_tiledata: LevelTileData, trafficlight: TrafficLight) -> (bool, i8) {
+  let amount = 3 * (entity.get_class(context).width as IScalar) >> 1;
   let wanted_pos = entity.pos + util::cossin(angle) * (amount as FScalar);
-  let level = &globals::get_game().level;
-  let wanted_tiledata = level::get_tiledata_for_pos(level, wanted_pos);
+  let wanted_tiledata = level::get_tiledata_for_pos(view.level, wanted_pos);
 
-  if !is_point_free(entity, wanted_pos) {
+  if !is_point_free(context, view, entity, wanted_pos) {
     return (false, wanted_tiledata);
   }
 
@@ -198,9 +218,7 @@ fn can_move_on_road(entity: &EntityBase, angle: Angle, last_tiledata: LevelTileD
   (true, wanted_tiledata)
 }
 
-fn get_lane(tilepos: Vec3i) -> Option<Vec3i> {
-  let level = &globals::get_game().level;
-
+fn get_lane(level: &Level, tilepos: Vec3i) -> Option<Vec3i> {
   let tiledata = get_tiledata_for_tilepos(level, tilepos);
   if tiledata < 10 || tiledata > 13 {
     return None;
@@ -232,11 +250,11 @@ fn get_lane(tilepos: Vec3i) -> Option<Vec3i> {
   Some(Vec3i::new2(x_direction, y_direction))
 }
 
-fn get_road_middle(pos: Vec3f) -> Option<Vec3f> {
+fn get_road_middle(level: &Level, pos: Vec3f) -> Option<Vec3f> {
   let tilepos = pos_to_tilepos(pos);
   let pos_tilepos = tilepos_to_pos(tilepos);
 
-  let lane = get_lane(tilepos);
+  let lane = get_lane(level, tilepos);
   if let Some(lane) = lane {
     /*if lane.x <= 0 && lane.y <= 0 {
       //return None;
@@ -275,9 +293,12 @@ fn get_turn_amount(entity: &EntityBase, delta: Time, wanted_angle: Angle) -> Ang
   }
 }
 
-fn move_to_middle_of_road(entity: &EntityBase, delta: Time) -> Vec3f {
-  let road_middle = get_road_middle(entity.pos);
+// lane_offset moves the middle to the right of the vehicle (left if negative)
+fn move_to_middle_of_road(level: &Level, entity: &EntityBase, lane_offset: FScalar, delta: Time) -> Vec3f {
+  let road_middle = get_road_middle(level, entity.pos);
   if let Some(road_middle) = road_middle {
+    let road_middle = road_middle + util::cossin(entity.angle + util::HALF_PI) * lane_offset;
+
     //println!("{:?}", road_middle - entity.pos);
     let diff = road_middle - entity.pos;
     let diff_len = diff.len2();
@@ -293,33 +314,116 @@ fn move_to_middle_of_road(entity: &EntityBase, delta: Time) -> Vec3f {
   }
 }
 
-fn get_road_spawn(pos: Vec3f) -> Option<Vec3f> {
-  let game = globals::get_game();
-  let tiledata = level::get_tiledata_for_pos(&game.level, pos);
+// not in the original game
+// how far ahead drivers look for things in the way
+const LOOKAHEAD_DISTANCE: FScalar = 64.;
+// distance kept to whatever is ahead
+const MIN_GAP: FScalar = 6.;
+// extra speed per unit of distance to whatever is ahead
+const FOLLOW_RATE: FScalar = 2.;
+// slower vehicles this close ahead get overtaken
+const OVERTAKE_GAP: FScalar = 24.;
+const OVERTAKE_SPEED_DIFFERENCE: FScalar = 15.;
+// from the middle of a two lane road
+const OVERTAKE_OFFSET: FScalar = (util::TILESIZE * 2 / 3) as FScalar;
+// honks at people standing in the way for this long
+const HONK_DELAY: Time = 1500;
+const HONK_GAP: FScalar = 16.;
+const HONK_DRAW_TIME: Time = 300;
+const HONK_COLOR: Color = Color { r: 255, g: 255, b: 0, a: 255 };
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Obstacle {
+  // free space between the two
+  gap: FScalar,
+  // in the same direction as the vehicle looking for it
+  speed: FScalar,
+  is_vehicle: bool
+}
+
+fn has_two_lanes(level: &Level, pos: Vec3f) -> bool {
+  match get_lane(level, pos_to_tilepos(pos)) {
+    Some(lane) => lane.x != 0 || lane.y != 0,
+    None => false
+  }
+}
+
+// the closest entity in front of the vehicle, shift moves the searched lane to the right
+fn find_obstacle(context: &globals::Context, view: &screen::GameView, entity: &EntityBase, shift: FScalar) -> Option<Obstacle> {
+  let class = entity.get_class(context);
+  let forward = util::cossin(entity.angle);
+  let right = util::cossin(entity.angle + util::HALF_PI);
+
+  let mut nearest: Option<Obstacle> = None;
+
+  for other in view.other_entities() {
+    if other.base.hidden ||
+      other.base.stance == EntityStance::Riding ||
+      other.base.stance == EntityStance::Dead && other.base.entity_type.is_person() {
+        continue;
+      }
+
+    let is_vehicle = other.base.entity_type.is_vehicle();
+    if !is_vehicle && !other.base.entity_type.is_person() {
+      continue;
+    }
+
+    let other_class = other.get_class(context);
+    let other_size = if is_vehicle {
+      util::fmax(other_class.width, other_class.height)
+    } else {
+      other_class.width
+    };
+
+    let diff = other.base.pos - entity.pos;
+    let ahead = util::vec_dot(diff, forward);
+    let side = util::vec_dot(diff, right) - shift;
+
+    if ahead <= 0. || ahead > LOOKAHEAD_DISTANCE + class.width + other_size || side.abs() > class.height + other_size {
+      continue;
+    }
+
+    let gap = ahead - class.width - other_size;
+    if let Some(nearest) = nearest {
+      if nearest.gap <= gap {
+        continue;
+      }
+    }
+
+    nearest = Some(Obstacle {
+      gap,
+      speed: other.base.speed * util::vec_dot(util::cossin(other.base.angle), forward),
+      is_vehicle
+    });
+  }
+
+  nearest
+}
+
+fn get_road_spawn(view: &screen::GameView, pos: Vec3f) -> Option<Vec3f> {
+  let tiledata = level::get_tiledata_for_pos(view.level, pos);
 
   if tiledata >= 10 && tiledata <= 13 {
-    if game.entity_spawn_counter % 4 == tiledata as usize - 10 {
-      return get_road_middle(pos);
+    if view.entity_spawn_counter % 4 == tiledata as usize - 10 {
+      return get_road_middle(view.level, pos);
     }
   }
 
   None
 }
 
-fn is_point_free(self_entity: &EntityBase, point: Vec3f) -> bool {
-  let game = globals::get_game();
-
-  for entity in game.entities.iter() {
-    if entity.base.id != self_entity.id && !entity.base.hidden {
+fn is_point_free(context: &globals::Context, view: &screen::GameView, self_entity: &EntityBase, point: Vec3f) -> bool {
+  for entity in view.other_entities() {
+    if !entity.base.hidden {
       if (entity.base.entity_type == EntityType::Type8 ||
           entity.base.entity_type == EntityType::PlayerVehicle ||
           entity.base.entity_type == EntityType::MovingVehicle ||
           entity.base.entity_type == EntityType::PoliceCar) {
-        if (point - entity.base.pos).abs().max2() < entity.get_class().width {
+        if (point - entity.base.pos).abs().max2() < entity.get_class(context).width {
           return false;
         }
       } else if entity.base.entity_type == EntityType::Player {
-        if (point - entity.base.pos).abs().max2() < self_entity.get_class().height {
+        if (point - entity.base.pos).abs().max2() < self_entity.get_class(context).height {
           return false;
         }
       }
@@ -333,9 +437,7 @@ fn is_point_free(self_entity: &EntityBase, point: Vec3f) -> bool {
 // TODO:
 //   start with the tile closest to the angle of the car
 //   find all nearest blocks first
-fn find_angle_to_road(tilepos: Vec3i) -> Option<Angle> {
-  let game = globals::get_game();
-
+fn find_angle_to_road(level: &Level, tilepos: Vec3i) -> Option<Angle> {
   for sign_x in 0..2 {
     for search_x in 0..2 {
       let mut tile_x = search_x + 1;
@@ -354,7 +456,7 @@ fn find_angle_to_road(tilepos: Vec3i) -> Option<Angle> {
           pos.x += tile_x;
           pos.y += tile_y;
 
-          let tiledata = level::get_tiledata_for_tilepos(&game.level, pos.into());
+          let tiledata = level::get_tiledata_for_tilepos(level, pos.into());
           if is_road(tiledata) {
             return Some(util::vec_angle((pos - tilepos).into()))
           }
@@ -366,33 +468,519 @@ fn find_angle_to_road(tilepos: Vec3i) -> Option<Angle> {
   None
 }
 
+// not in the original game, which only moves vehicles forward at a single speed.
+// vehicles driven by the player or hit by something keep a velocity separate from their heading.
+
+// class weight the constants below are balanced for
+const REFERENCE_WEIGHT: FScalar = 100.;
+// units per second^2
+const ENGINE_ACCELERATION: FScalar = 120.;
+const BRAKE_DECELERATION: FScalar = 250.;
+const HANDBRAKE_DECELERATION: FScalar = 80.;
+const MAX_FORWARD_SPEED: FScalar = 200.;
+const MAX_REVERSE_SPEED: FScalar = 60.;
+// fraction of the speed lost per second
+const ROLLING_FRICTION: FScalar = 0.5;
+// fraction of the sideways speed removed by the tyres per second
+const LATERAL_GRIP: FScalar = 8.;
+const HANDBRAKE_GRIP: FScalar = 1.;
+// radians per second at full lock
+const STEERING_SPEED: Angle = 3.;
+const HANDBRAKE_STEERING: FScalar = 1.5;
+// below this speed steering has less effect
+const FULL_STEERING_SPEED: FScalar = 40.;
+// sideways speed at which the tyres start to slide
+const SLIDE_SPEED: FScalar = 25.;
+// getting hit this hard makes a vehicle lose control
+const LOSE_CONTROL_SPEED: FScalar = 60.;
+// below this speed a sliding vehicle stops
+const STOP_SPEED: FScalar = 2.;
+const MAX_SKIDMARKS: usize = 2048;
+const SKIDMARK_COLOR: Color = Color { r: 32, g: 32, b: 32, a: 255 };
+// how close the player has to be to get into a vehicle
+const ENTER_DISTANCE: FScalar = 32.;
+
+// fractions of the class health below which a vehicle smokes, then burns
+const SMOKE_HEALTH: FScalar = 0.5;
+const FIRE_HEALTH: FScalar = 0.25;
+// a burning vehicle loses a point of health this often, until it explodes
+const BURN_INTERVAL: Time = 100;
+// hits changing the velocity more than this damage the vehicle, by COLLISION_DAMAGE per unit of speed above it
+const COLLISION_DAMAGE_SPEED: FScalar = 50.;
+const COLLISION_DAMAGE: FScalar = 0.25;
+const EXPLOSION_RADIUS: FScalar = 48.;
+const EXPLOSION_DAMAGE: IScalar = 100;
+// how far an entity right next to the explosion is thrown in a single step, and how fast
+const EXPLOSION_PUSH: FScalar = 4.;
+const EXPLOSION_SPEED: FScalar = 150.;
+// police cars drive to alarms this close, but not to ones they're already at
+const POLICE_RESPONSE_RADIUS: FScalar = 240.;
+const POLICE_ARRIVED_RADIUS: FScalar = 48.;
+const POLICE_RESPONSE_SPEED: FScalar = 70.;
+
+// handled by the GameScreen once every entity has been stepped
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Explosion {
+  pub pos: Vec3f,
+  pub source: EntityId
+}
+
+impl Explosion {
+  // damage and push for an entity at pos, if it's close enough
+  // damage, push and impact
+  pub fn get_effect(&self, pos: Vec3f) -> Option<(IScalar, Vec3f, Vec3f)> {
+    let diff = pos - self.pos;
+    let distance = diff.len2();
+    if distance >= EXPLOSION_RADIUS {
+      return None;
+    }
+
+    let strength = 1. - distance / EXPLOSION_RADIUS;
+    let direction = if distance > 0. {
+      diff / distance
+    } else {
+      Vec3f::default()
+    };
+
+    Some(((EXPLOSION_DAMAGE as FScalar * strength).ceil() as IScalar,
+          direction * (EXPLOSION_PUSH * strength),
+          direction * (EXPLOSION_SPEED * strength)))
+  }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct VehicleControls {
+  // -1 (reverse) to 1
+  pub throttle: FScalar,
+  // -1 (left) to 1
+  pub steering: FScalar,
+  pub handbrake: bool
+}
+
+impl VehicleControls {
+  pub fn from_input(input: &input::InputContext) -> Self {
+    let mut controls = VehicleControls::default();
+
+    if input.using_controller && input.left_stick.len2() > 0. {
+      controls.steering = input.left_stick.x;
+      controls.throttle = -input.left_stick.y;
+    } else {
+      if input.keys.contains_key(&input::InputKey::Up) {
+        controls.throttle = 1.;
+      } else if input.keys.contains_key(&input::InputKey::Down) {
+        controls.throttle = -1.;
+      }
+
+      if input.keys.contains_key(&input::InputKey::Left) {
+        controls.steering = -1.;
+      } else if input.keys.contains_key(&input::InputKey::Right) {
+        controls.steering = 1.;
+      }
+    }
+
+    controls.handbrake = input.keys.contains_key(&input::InputKey::Handbrake);
+
+    controls
+  }
+}
+
+// tyre marks left on the road, oldest first
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Skidmarks {
+  marks: VecDeque<Vec3i>
+}
+
+impl Skidmarks {
+  pub fn new() -> Self {
+    Skidmarks::default()
+  }
+
+  pub fn add(&mut self, pos: Vec3f) {
+    if self.marks.len() >= MAX_SKIDMARKS {
+      self.marks.pop_front();
+    }
+
+    self.marks.push_back(Vec3i::from(pos));
+  }
+
+  pub fn draw(&self, context: &mut globals::Context) {
+    context.platform.set_color(SKIDMARK_COLOR);
+
+    for mark in self.marks.iter() {
+      context.platform.fill_rect(mark.x - 1, mark.y - 1, 2, 2);
+    }
+  }
+}
+
+fn can_be_entered(entity: &Entity) -> bool {
+  entity.base.entity_type.is_vehicle() &&
+    entity.base.entity_type != EntityType::PlayerVehicle &&
+    !entity.base.hidden &&
+    entity.base.stance != EntityStance::Dead
+}
+
+// the closest vehicle the player can get into
+pub fn find_vehicle_to_enter(entities: &[Entity], pos: Vec3f) -> Option<usize> {
+  let mut nearest = None;
+  let mut nearest_distance = ENTER_DISTANCE;
+
+  for (i, entity) in entities.iter().enumerate() {
+    if !can_be_entered(entity) {
+      continue;
+    }
+
+    let distance = (entity.base.pos - pos).len2();
+    if distance < nearest_distance {
+      nearest_distance = distance;
+      nearest = Some(i);
+    }
+  }
+
+  nearest
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct VehicleData {
   last_tiledata: LevelTileData,
-  wanted_speed: FScalar
+  wanted_speed: FScalar,
+
+  velocity: Vec3f,
+  // how much collisions changed the velocity since the last step, and the hardest single hit
+  collision_impact: Vec3f,
+  collision_speed: FScalar,
+  burn_millis: Time,
+
+  // to the right of the middle of the road, while overtaking
+  lane_offset: FScalar,
+  blocked_millis: Time,
+  honk_millis: Time,
+
+  navigation: Option<pathfinding::Navigation>
+}
+
+// smoking, and drawn with the damaged sprite if the clip has one
+pub fn is_damaged(context: &globals::Context, entity: &EntityBase) -> bool {
+  let max_health = entity.get_class(context).health as FScalar;
+
+  max_health > 0. && (entity.health as FScalar) < max_health * SMOKE_HEALTH
+}
+
+fn get_mass_factor(context: &globals::Context, entity: &EntityBase) -> FScalar {
+  let mass = util::fmax(1., entity.get_class(context).weight as FScalar);
+
+  util::fmin(2., util::fmax(0.5, REFERENCE_WEIGHT / mass))
+}
+
+// slows speed towards 0 by amount, without going past it
+fn decelerate(speed: FScalar, amount: FScalar) -> FScalar {
+  if speed > 0. {
+    util::fmax(0., speed - amount)
+  } else {
+    util::fmin(0., speed + amount)
+  }
 }
 
 impl VehicleData {
   pub fn new() -> Self {
     VehicleData {
       last_tiledata: -1,
-      wanted_speed: 0.
+      wanted_speed: 0.,
+
+      velocity: Vec3f::default(),
+      collision_impact: Vec3f::default(),
+      collision_speed: 0.,
+      burn_millis: 0,
+
+      lane_offset: 0.,
+      blocked_millis: 0,
+      honk_millis: HONK_DRAW_TIME,
+
+      navigation: None
+    }
+  }
+
+  // adds what collisions did to the velocity, hard hits damage the vehicle
+  fn apply_collision_impact(&mut self, context: &globals::Context, entity: &mut EntityBase) {
+    self.velocity = self.velocity + self.collision_impact;
+
+    // hits from opposite sides don't cancel each other out
+    if self.collision_speed > COLLISION_DAMAGE_SPEED {
+      let amount = ((self.collision_speed - COLLISION_DAMAGE_SPEED) * COLLISION_DAMAGE).ceil() as IScalar;
+      self.damage(context, entity, amount);
+    }
+
+    self.collision_impact = Vec3f::default();
+    self.collision_speed = 0.;
+  }
+
+  // smoke, fire, and the explosion once the health runs out
+  fn step_damage(&mut self, context: &mut globals::Context, view: &mut screen::GameView, entity: &mut EntityBase, delta: Time) {
+    let max_health = entity.get_class(context).health as IScalar;
+    if max_health <= 0 {
+      return;
+    }
+
+    if !entity.broken && entity.health <= 0 {
+      entity.broken = true;
+      entity.speed = 0.;
+      entity.set_new_stance(EntityStance::Dead);
+
+      view.explosions.push(Explosion {
+        pos: entity.pos,
+        source: entity.id
+      });
+      view.particles.explode(context, entity.pos);
+      return;
+    }
+
+    let health = entity.health as FScalar / max_health as FScalar;
+
+    if health < FIRE_HEALTH && !entity.broken {
+      self.burn_millis += delta;
+      while self.burn_millis >= BURN_INTERVAL {
+        self.burn_millis -= BURN_INTERVAL;
+        entity.health -= 1;
+      }
+
+      if util::pick_cosmetic_int(context, 2) == 0 {
+        view.particles.emit(context, particle::ParticleType::Fire, entity.pos);
+      }
+    }
+
+    if health < SMOKE_HEALTH && util::pick_cosmetic_int(context, 4) == 0 {
+      view.particles.emit(context, particle::ParticleType::Smoke, entity.pos);
+    }
+  }
+
+  fn add_skidmarks(&self, context: &globals::Context, view: &mut screen::GameView, entity: &EntityBase) {
+    let class = entity.get_class(context);
+    let forward = util::cossin(entity.angle);
+    let right = util::cossin(entity.angle + util::HALF_PI);
+
+    // rear wheels
+    let rear = entity.pos - forward * (class.width * 0.7);
+    view.skidmarks.add(rear + right * (class.height * 0.7));
+    view.skidmarks.add(rear - right * (class.height * 0.7));
+  }
+
+  // returns true while the tyres are sliding
+  fn step_physics(&mut self, context: &globals::Context, view: &mut screen::GameView, entity: &mut EntityBase,
+                  controls: VehicleControls, delta: Time) -> bool {
+    let seconds = delta as FScalar / 1000.;
+    let mass_factor = get_mass_factor(context, entity);
+
+    let forward = util::cossin(entity.angle);
+    let right = util::cossin(entity.angle + util::HALF_PI);
+
+    let mut forward_speed = util::vec_dot(self.velocity, forward);
+    let mut lateral_speed = util::vec_dot(self.velocity, right);
+
+    if controls.throttle > 0. && forward_speed < 0. || controls.throttle < 0. && forward_speed > 0. {
+      forward_speed = decelerate(forward_speed, BRAKE_DECELERATION * controls.throttle.abs() * seconds);
+    } else {
+      forward_speed += ENGINE_ACCELERATION * mass_factor * controls.throttle * seconds;
+    }
+
+    forward_speed = util::fmin(MAX_FORWARD_SPEED, util::fmax(-MAX_REVERSE_SPEED, forward_speed));
+    forward_speed *= util::fmax(0., 1. - ROLLING_FRICTION * seconds);
+
+    let grip = if controls.handbrake {
+      forward_speed = decelerate(forward_speed, HANDBRAKE_DECELERATION * seconds);
+      HANDBRAKE_GRIP
+    } else {
+      LATERAL_GRIP
+    };
+
+    lateral_speed *= util::fmax(0., 1. - grip * seconds);
+
+    // turning needs the vehicle to move, and is reversed when backing up
+    let mut steering = controls.steering * STEERING_SPEED * seconds *
+      util::fmin(1., forward_speed.abs() / FULL_STEERING_SPEED);
+    if forward_speed < 0. {
+      steering = -steering;
+    }
+
+    if controls.handbrake {
+      steering *= HANDBRAKE_STEERING;
+    }
+
+    // the velocity keeps the old heading, the grip pulls it towards the new one on the next steps
+    self.velocity = forward * forward_speed + right * lateral_speed;
+    entity.angle = util::normalize_angle(entity.angle + steering);
+
+    if controls.throttle == 0. && self.velocity.len2() < STOP_SPEED {
+      self.velocity = Vec3f::default();
+    }
+
+    entity.pos = entity.pos + self.velocity * seconds;
+    entity.speed = forward_speed;
+    entity.update_pos();
+
+    let sliding = lateral_speed.abs() > SLIDE_SPEED ||
+      (controls.handbrake && forward_speed.abs() > SLIDE_SPEED);
+
+    if sliding {
+      self.add_skidmarks(context, view, entity);
     }
+
+    sliding
   }
 
-  fn step_drive_along_road(&mut self, entity: &mut EntityBase, delta: Time) {
-    entity.update_prev();
+  fn step_player_vehicle(&mut self, context: &globals::Context, view: &mut screen::GameView, entity: &mut EntityBase, delta: Time) {
+    self.apply_collision_impact(context, entity);
+
+    let controls = VehicleControls::from_input(&context.input);
+    if self.step_physics(context, view, entity, controls, delta) {
+      entity.set_new_stance(EntityStance::Sliding);
+    } else if self.velocity.len2() > 0. {
+      entity.set_new_stance(EntityStance::Running);
+    } else {
+      entity.set_new_stance(EntityStance::Standing);
+    }
+  }
+
+  // pushed around by a collision, until it comes to a stop
+  fn step_sliding(&mut self, context: &globals::Context, view: &mut screen::GameView, entity: &mut EntityBase, delta: Time) {
+    self.apply_collision_impact(context, entity);
+
+    self.step_physics(context, view, entity, VehicleControls::default(), delta);
+
+    if self.velocity.len2() < STOP_SPEED {
+      self.velocity = Vec3f::default();
+      entity.speed = 0.;
+
+      if entity.entity_type.is_npc() {
+        entity.set_new_stance(EntityStance::Running);
+      } else {
+        entity.set_new_stance(EntityStance::Standing);
+      }
+    }
+  }
+
+  // anything not driven by the player
+  fn step_road_vehicle(&mut self, context: &globals::Context, view: &mut screen::GameView, entity: &mut EntityBase, delta: Time) {
+    self.apply_collision_impact(context, entity);
+
+    // a hard enough hit makes the vehicle lose control
+    let forward_velocity = util::cossin(entity.angle) * entity.speed;
+    if (self.velocity - forward_velocity).len2() > LOSE_CONTROL_SPEED {
+      entity.set_new_stance(EntityStance::Sliding);
+      return self.step_sliding(context, view, entity, delta);
+    }
+
+    if entity.entity_type == EntityType::PoliceCar {
+      self.check_alarms(view, entity);
+    }
+
+    if self.navigation.is_some() {
+      self.step_navigation(context, view, entity, delta);
+    } else {
+      self.step_drive_along_road(context, view, entity, delta);
+    }
 
+    self.velocity = util::cossin(entity.angle) * entity.speed;
+  }
+
+  fn check_alarms(&mut self, view: &mut screen::GameView, entity: &mut EntityBase) {
+    if self.navigation.is_some() || entity.stance != EntityStance::Running {
+      return;
+    }
+
+    for alarm in view.alarms.iter() {
+      let distance = (alarm.pos - entity.pos).len2();
+      if distance < POLICE_ARRIVED_RADIUS || distance > POLICE_RESPONSE_RADIUS {
+        continue;
+      }
+
+      self.navigation = pathfinding::Navigation::new(view.pathfinder, view.level, pathfinding::Mover::Vehicle, entity.pos, alarm.pos);
+      if let Some(ref navigation) = self.navigation {
+        entity.route.set_route(navigation.get_route(), 0.);
+        entity.following_route = true;
+        self.lane_offset = 0.;
+        return;
+      }
+    }
+  }
+
+  // drives along the planned route, still braking for whatever is in the way
+  fn step_navigation(&mut self, context: &globals::Context, view: &mut screen::GameView, entity: &mut EntityBase, delta: Time) {
+    self.wanted_speed = POLICE_RESPONSE_SPEED;
+    self.react_to_traffic(context, view, entity, delta);
+    self.lane_offset = 0.;
+    self.accelerate_to_wanted_speed(entity, delta);
+
+    entity.step_route(delta);
+
+    if entity.route.route.is_none() {
+      // back to driving along the road from wherever it ended up
+      self.navigation = None;
+      self.last_tiledata = level::get_tiledata_for_pos(view.level, entity.pos);
+    }
+
+    self.honk_millis += delta;
+  }
+
+  // slows down for whatever is ahead, overtakes slow vehicles and honks at people in the way
+  fn react_to_traffic(&mut self, context: &globals::Context, view: &mut screen::GameView, entity: &EntityBase, delta: Time) {
+    let two_lanes = has_two_lanes(view.level, entity.pos);
+    if !two_lanes {
+      self.lane_offset = 0.;
+    }
+
+    let obstacle = match find_obstacle(context, view, entity, 0.) {
+      Some(obstacle) => obstacle,
+      None => {
+        self.blocked_millis = 0;
+
+        // back to the middle once it's free
+        if self.lane_offset != 0. && find_obstacle(context, view, entity, -self.lane_offset).is_none() {
+          self.lane_offset = 0.;
+        }
+
+        return;
+      }
+    };
+
+    let cruise_speed = self.wanted_speed;
+    let follow_speed = util::fmax(0., obstacle.speed + (obstacle.gap - MIN_GAP) * FOLLOW_RATE);
+    self.wanted_speed = util::fmin(self.wanted_speed, follow_speed);
+
+    if (obstacle.is_vehicle && two_lanes && self.lane_offset == 0. &&
+        obstacle.gap < OVERTAKE_GAP &&
+        obstacle.speed < cruise_speed - OVERTAKE_SPEED_DIFFERENCE) {
+      for offset in [OVERTAKE_OFFSET, -OVERTAKE_OFFSET].iter() {
+        if find_obstacle(context, view, entity, *offset).is_none() {
+          self.lane_offset = *offset;
+          break;
+        }
+      }
+    }
+
+    if !obstacle.is_vehicle && obstacle.gap < HONK_GAP {
+      self.blocked_millis += delta;
+
+      if self.blocked_millis >= HONK_DELAY {
+        self.blocked_millis = 0;
+        self.honk_millis = 0;
+
+        view.new_alarms.push(person::Alarm {
+          pos: entity.pos + util::cossin(entity.angle) * entity.get_class(context).width
+        });
+      }
+    } else {
+      self.blocked_millis = 0;
+    }
+  }
+
+  fn step_drive_along_road(&mut self, context: &globals::Context, view: &mut screen::GameView, entity: &mut EntityBase, delta: Time) {
     self.wanted_speed = 0.;
 
     if entity.stance == EntityStance::Running {
-      let game = globals::get_game();
-
-      let tiledata = level::get_tiledata_for_pos(&game.level, entity.pos);
+      let tiledata = level::get_tiledata_for_pos(view.level, entity.pos);
 
-      let road_direction = get_road_direction(tiledata, game.vehicle_state.trafficlight);
+      let road_direction = get_road_direction(tiledata, view.vehicle_state.trafficlight);
       if let Some(road_direction) = road_direction {
-        let (can_move, new_tiledata) = can_move_on_road(entity, road_direction, self.last_tiledata, game.vehicle_state.trafficlight);
+        let (can_move, new_tiledata) = can_move_on_road(context, view, entity, road_direction, self.last_tiledata, view.vehicle_state.trafficlight);
         if can_move {
           self.wanted_speed = 25.;
 
@@ -406,7 +994,7 @@ impl VehicleData {
 
           self.last_tiledata = new_tiledata;
         }
-      } else if let Some(angle) = find_angle_to_road(level::pos_to_tilepos(entity.pos)) {
+      } else if let Some(angle) = find_angle_to_road(view.level, level::pos_to_tilepos(entity.pos)) {
         // TODO: merge these "turn amount" functions to avoid code duplication with above
         let turn_amount = get_turn_amount(entity, delta, angle);
         if turn_amount != 0. {
@@ -417,9 +1005,12 @@ impl VehicleData {
         }
       }
 
-      entity.pos = move_to_middle_of_road(entity, delta);
+      self.react_to_traffic(context, view, entity, delta);
+
+      entity.pos = move_to_middle_of_road(view.level, entity, self.lane_offset, delta);
     }
 
+    self.honk_millis += delta;
     self.move_vehicle(entity, delta);
   }
 
@@ -442,52 +1033,61 @@ impl VehicleData {
     }
 
     entity.move_forward(delta);
-    // TODO: calculate skidmarks
   }
 
-  fn init_simple_vehicle(&mut self, entity: &mut EntityBase) {
+  fn init_simple_vehicle(&mut self, context: &mut globals::Context, entity: &mut EntityBase) {
     // motorcycle
     if entity.class == 38 || entity.class == 39 {
-      entity.gender = EntityBase::pick_gender();
-      entity.palette = EntityBase::pick_npc_person_palette();
+      entity.gender = EntityBase::pick_gender(context);
+      entity.palette = EntityBase::pick_npc_person_palette(context);
     } else {
-      entity.palette = util::pick_int(2) + 1;
+      entity.palette = util::pick_cosmetic_int(context, 2) + 1;
     }
   }
 
-  fn draw_basic_vehicle(&self, entity: &EntityBase, palette: PaletteId) {
-    let context = globals::get_context();
-    let class = entity.get_class();
+  fn draw_basic_vehicle(&self, context: &mut globals::Context, entity: &EntityBase, palette: PaletteId) {
+    let current_sprite = match entity.animation.get_sprite(context, entity.get_draw_angle(context)) {
+      Some(sprite) => sprite,
+      None => return
+    };
 
-    let clip = &context.data.clips[class.clip as usize];
-    let clip_angle = &clip[util::get_angle_in_clip(entity.angle, clip.len())];
-    let current_sprite = clip_angle[0];
+    let imageid = sprite::get_image_from_sprite(context, current_sprite).unwrap();
+    let pos = entity.get_draw_pos(context);
+    sprite::draw_sprite_palette(context, current_sprite, pos.into(), 0, &vec![(imageid, palette)]);
+  }
+
+  fn draw_honk(&self, context: &mut globals::Context, entity: &EntityBase) {
+    let forward = util::cossin(entity.get_draw_angle(context));
+    let front = entity.get_draw_pos(context) + forward * (entity.get_class(context).width + 4.);
 
-    let imageid = sprite::get_image_from_sprite(current_sprite).unwrap();
-    sprite::draw_sprite_palette(current_sprite, entity.pos.into(), 0, &vec![(imageid, palette)]);
+    context.platform.set_color(HONK_COLOR);
+    for i in 0..2 {
+      let pos = Vec3i::from(front + forward * (i as FScalar * 4.));
+      context.platform.fill_rect(pos.x - 1, pos.y - 1, 2, 2);
+    }
   }
 
-  fn draw_motorcycle(&self, entity: &EntityBase) {
-    self.draw_basic_vehicle(entity, 0);
+  fn draw_motorcycle(&self, context: &mut globals::Context, entity: &EntityBase) {
+    self.draw_basic_vehicle(context, entity, 0);
 
     // TODO: change gender if image with palette is loaded?
     if entity.stance != EntityStance::Standing || true {
-      let context = globals::get_context();
       let clip = &context.data.clips[entity.gender.get_clip_id() as usize];
-      let clip_angle = &clip[util::get_angle_in_clip(entity.angle, clip.len())];
+      let clip_angle = &clip[util::get_angle_in_clip(entity.get_draw_angle(context), clip.len())];
       let current_sprite = clip_angle[0];
 
-      let imageid = sprite::get_image_from_sprite(current_sprite).unwrap();
-      sprite::draw_sprite_palette(current_sprite, entity.pos.into(), 0, &vec![(imageid, entity.palette)]);
+      let imageid = sprite::get_image_from_sprite(context, current_sprite).unwrap();
+      let pos = entity.get_draw_pos(context);
+      sprite::draw_sprite_palette(context, current_sprite, pos.into(), 0, &vec![(imageid, entity.palette)]);
     }
   }
 }
 
 impl EntityData for VehicleData {
-  fn init(&mut self, entity: &mut EntityBase) {
+  fn init(&mut self, context: &mut globals::Context, entity: &mut EntityBase) {
     if (entity.entity_type == EntityType::Type8 ||
         entity.entity_type == EntityType::MovingVehicle) {
-      self.init_simple_vehicle(entity);
+      self.init_simple_vehicle(context, entity);
     }
 
     if entity.entity_type == EntityType::MovingVehicle {
@@ -495,13 +1095,12 @@ impl EntityData for VehicleData {
     }
   }
 
-  fn get_collision_info(&self, entity: &EntityBase) -> Option<collision::ShapeInfo> {
+  fn get_collision_info(&self, context: &globals::Context, entity: &EntityBase) -> Option<collision::ShapeInfo> {
     if (entity.entity_type == EntityType::Type8 ||
         entity.entity_type == EntityType::PlayerVehicle ||
         entity.entity_type == EntityType::MovingVehicle ||
         entity.entity_type == EntityType::EnemyVehicle ||
         entity.entity_type == EntityType::PoliceCar) {
-      let context = globals::get_context();
       let class = &context.data.classes[entity.class as usize];
 
       Some(collision::ShapeInfo {
@@ -513,47 +1112,80 @@ impl EntityData for VehicleData {
     }
   }
 
-  fn spawn(&mut self, entity: &mut EntityBase, pos: Vec3f) -> Option<Vec3f> {
-    if !is_point_free(entity, pos) {
+  fn spawn(&mut self, context: &mut globals::Context, view: &screen::GameView, entity: &mut EntityBase, pos: Vec3f) -> Option<Vec3f> {
+    if !is_point_free(context, view, entity, pos) {
       return None;
     }
 
-    if let Some(pos) = get_road_spawn(pos) {
+    if let Some(pos) = get_road_spawn(view, pos) {
       entity.pos = pos;
     } else {
       return None;
     }
 
-    let game = globals::get_game();
-    let tiledata = level::get_tiledata_for_pos(&game.level, entity.pos);
+    let tiledata = level::get_tiledata_for_pos(view.level, entity.pos);
 
-    entity.pos = move_to_middle_of_road(entity, 1000);
+    entity.pos = move_to_middle_of_road(view.level, entity, 0., 1000);
 
-    if let Some(direction) = get_road_direction(tiledata, game.vehicle_state.trafficlight) {
+    if let Some(direction) = get_road_direction(tiledata, view.vehicle_state.trafficlight) {
       entity.angle = direction;
     }
 
     self.last_tiledata = tiledata;
-    if !can_move_on_road(entity, entity.angle, tiledata, game.vehicle_state.trafficlight).0 {
+    if !can_move_on_road(context, view, entity, entity.angle, tiledata, view.vehicle_state.trafficlight).0 {
       return None;
     }
 
     entity.hidden = false;
     entity.stance = EntityStance::Running;
 
+    self.velocity = Vec3f::default();
+    self.collision_impact = Vec3f::default();
+    self.collision_speed = 0.;
+
     Some(entity.pos)
   }
 
-  fn step(&mut self, entity: &mut EntityBase, delta: Time) {
-    self.step_drive_along_road(entity, delta);
+  fn step(&mut self, context: &mut globals::Context, view: &mut screen::GameView, entity: &mut EntityBase, delta: Time) {
+    self.step_damage(context, view, entity, delta);
+
+    if entity.broken {
+      entity.speed = 0.;
+      self.velocity = Vec3f::default();
+      self.collision_impact = Vec3f::default();
+      self.collision_speed = 0.;
+    } else if entity.entity_type == EntityType::PlayerVehicle {
+      self.step_player_vehicle(context, view, entity, delta);
+    } else if entity.stance == EntityStance::Sliding {
+      self.step_sliding(context, view, entity, delta);
+    } else {
+      self.step_road_vehicle(context, view, entity, delta);
+    }
+  }
+
+  fn on_collision(&mut self, _entity: &mut EntityBase, _push: Vec3f, impact: Vec3f) {
+    self.collision_impact = self.collision_impact + impact;
+    self.collision_speed = util::fmax(self.collision_speed, impact.len2());
   }
 
-  fn draw(&self, entity: &EntityBase) {
+  fn damage(&mut self, context: &globals::Context, entity: &mut EntityBase, amount: IScalar) {
+    // classes without health can't be destroyed
+    if entity.broken || entity.get_class(context).health <= 0 {
+      return;
+    }
+
+    entity.health -= amount;
+  }
+
+  fn draw(&self, context: &mut globals::Context, entity: &EntityBase) {
     if entity.class == 38 || entity.class == 39 {
-      return self.draw_motorcycle(entity);
+      return self.draw_motorcycle(context, entity);
     }
 
-    // TODO: if broken (flags 0x2) then draw broken car
-    self.draw_basic_vehicle(entity, entity.palette);
+    self.draw_basic_vehicle(context, entity, entity.palette);
+
+    if self.honk_millis < HONK_DRAW_TIME {
+      self.draw_honk(context, entity);
+    }
   }
 }
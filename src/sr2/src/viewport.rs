@@ -0,0 +1,131 @@
+use super::*;
+
+// not in the original game, which always draws to the phone's screen
+
+// the phone screen the game was made for
+pub const VIRTUAL_SIZE: Vec3i = Vec3i { x: 240, y: 320, z: 0 };
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ScaleMode {
+  // whole multiples of the virtual size only, every pixel has the same size
+  Integer,
+  // as large as fits in the window
+  Aspect
+}
+
+// the screen is drawn at the virtual size, then scaled to the window with black bars around it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Viewport {
+  pub size: Vec3i,
+  pub mode: ScaleMode,
+  pub window_size: Vec3i,
+  target: Option<PlatformId>,
+  // the parts of a pixel the mouse moved, so slow movements still add up when scaled down
+  mouse_remainder: Vec3f
+}
+
+impl Viewport {
+  pub fn new(mode: ScaleMode, window_size: Vec3i) -> Self {
+    Viewport {
+      size: VIRTUAL_SIZE,
+      mode,
+      window_size,
+      target: None,
+      mouse_remainder: Vec3f::default()
+    }
+  }
+
+  pub fn get_scale(&self) -> FScalar {
+    let scale_x = self.window_size.x as FScalar / self.size.x as FScalar;
+    let scale_y = self.window_size.y as FScalar / self.size.y as FScalar;
+    let scale = scale_x.min(scale_y);
+
+    match self.mode {
+      // too small windows still get the whole screen
+      ScaleMode::Integer => scale.floor().max(1.),
+      ScaleMode::Aspect => scale
+    }
+  }
+
+  // where the screen ends up in the window, as position and size
+  pub fn get_window_rect(&self) -> (Vec3i, Vec3i) {
+    let scale = self.get_scale();
+    let size = Vec3i::new2((self.size.x as FScalar * scale) as IScalar, (self.size.y as FScalar * scale) as IScalar);
+
+    ((self.window_size - size) / 2, size)
+  }
+
+  pub fn window_pos_to_screen_pos(&self, pos: Vec3i) -> Vec3i {
+    let (start, _) = self.get_window_rect();
+    let scale = self.get_scale();
+
+    Vec3i::from(Vec3f::from(pos - start) / scale)
+  }
+
+  // mouse positions are moved into the screen, so nothing else has to know about the window
+  pub fn handle_event(&mut self, event: Event) -> Event {
+    if let Event::Resize(window_size) = event {
+      self.window_size = window_size;
+    }
+
+    match event {
+      Event::MousePos { pos, delta } => {
+        let delta = Vec3f::from(delta) / self.get_scale() + self.mouse_remainder;
+        let whole = Vec3i::from(delta);
+        self.mouse_remainder = delta - Vec3f::from(whole);
+
+        Event::MousePos {
+          pos: self.window_pos_to_screen_pos(pos),
+          delta: whole
+        }
+      },
+      Event::Resize(_) => Event::Resize(self.size),
+      _ => event
+    }
+  }
+
+  // everything drawn until end() goes to the screen at the virtual size
+  pub fn begin(&mut self, platform: &mut Platform) {
+    if self.target.is_none() {
+      self.target = platform.new_render_target(self.size);
+    }
+
+    // drawn straight to the window without a target
+    if self.target.is_some() {
+      platform.set_render_target(self.target);
+    }
+
+    platform.reset();
+  }
+
+  pub fn end(&mut self, platform: &mut Platform) {
+    let target = match self.target {
+      Some(target) => target,
+      None => return
+    };
+
+    platform.set_render_target(None);
+    platform.reset();
+
+    platform.set_color(Color { r: 0, g: 0, b: 0, a: 255 });
+    platform.clear();
+
+    let (pos, size) = self.get_window_rect();
+    platform.draw_image_scaled(target, pos, size);
+  }
+
+  // made again on the next begin()
+  pub fn reset_target(&mut self, platform: &mut Platform) {
+    if let Some(target) = self.target.take() {
+      platform.unload_image(target);
+    }
+  }
+}
+
+// what the screens draw to, either the window or the viewport
+pub fn get_screen_size(context: &globals::Context) -> Vec3i {
+  match context.viewport {
+    Some(ref viewport) => viewport.size,
+    None => context.platform.get_size()
+  }
+}